@@ -0,0 +1,40 @@
+use advent_of_code_2022::solutions::{day5, day6, day8};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+fn day5_large_inputs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Day5 - large inputs");
+    group.sample_size(10);
+    for &(height, moves) in &[(100, 10_000), (1_000, 100_000), (10_000, 1_000_000)] {
+        let input = day5::generator(&day5::generate_large_input(9, height, moves, 2022));
+        let label = format!("{}x{}", height, moves);
+        group.bench_with_input(BenchmarkId::new("part1 simulate", &label), &input, |b, i| b.iter(|| day5::solve_part1(i)));
+        group.bench_with_input(BenchmarkId::new("part1 backtrack", &label), &input, |b, i| b.iter(|| day5::solve_part1_backtrack(i)));
+        group.bench_with_input(BenchmarkId::new("part2 simulate", &label), &input, |b, i| b.iter(|| day5::solve_part2(i)));
+        group.bench_with_input(BenchmarkId::new("part2 backtrack", &label), &input, |b, i| b.iter(|| day5::solve_part2_backtrack(i)));
+    }
+    group.finish();
+}
+
+fn day6_large_inputs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Day6 - large inputs");
+    for &length in &[10_000, 1_000_000] {
+        let stream = day6::generate_late_marker_stream(length, 2022);
+        group.bench_with_input(BenchmarkId::new("part2 window", length), &stream, |b, s| b.iter(|| day6::solve_part2(s)));
+        group.bench_with_input(BenchmarkId::new("part2 bytes", length), &stream, |b, s| b.iter(|| day6::solve_part2_bytes(s.as_bytes())));
+    }
+    group.finish();
+}
+
+fn day8_large_inputs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Day8 - large inputs");
+    group.sample_size(10);
+    for &size in &[100, 300, 1_000] {
+        let forest = day8::generator(&day8::generate_forest(size, size, 2022));
+        group.bench_with_input(BenchmarkId::new("part2 walk", size), &forest, |b, f| b.iter(|| day8::solve_part2(f)));
+        group.bench_with_input(BenchmarkId::new("part2 monotonic", size), &forest, |b, f| b.iter(|| day8::solve_part2_monotonic(f)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, day5_large_inputs, day6_large_inputs, day8_large_inputs);
+criterion_main!(benches);
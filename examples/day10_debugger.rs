@@ -0,0 +1,14 @@
+// Steps through a day 10 program from a terminal:
+//
+//     cargo run --example day10_debugger -- input/2022/day10.txt
+use std::{env, fs, io};
+
+use advent_of_code_2022::solutions::day10::{debug, generator};
+
+fn main() -> io::Result<()> {
+    let path = env::args().nth(1).unwrap_or_else(|| "input/2022/day10.txt".to_string());
+    let program = fs::read_to_string(path)?;
+    let ops = generator(program.trim_end()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let stdin = io::stdin();
+    debug(&ops, stdin.lock(), io::stdout())
+}
@@ -3,6 +3,6 @@ extern crate lazy_static;
 
 use aoc_runner_derive::aoc_lib;
 
-mod solutions;
+pub mod solutions;
 
 aoc_lib! { year = 2022, extra_alternatives = ["fnv"] }
\ No newline at end of file
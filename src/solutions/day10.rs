@@ -1,85 +1,674 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
-use num::integer::mod_floor;
+use lazy_static::lazy_static;
 use crate::solutions::day10::Op::{AddX, NoOp};
 
+lazy_static! {
+    // The capital letters of the 4x6 font Advent of Code draws its screens in.
+    static ref FONT: HashMap<String, char> = [
+        ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+        ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+        ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+        ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+        ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+        ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+        ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+        ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+        ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+        ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+        ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+        ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+        ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+        ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+        ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+        ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+        ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+    ].iter().map(|(letter, rows)| (rows.join("\n"), *letter)).collect();
+}
+
 #[derive(Eq, PartialEq, Clone, Debug)]
 pub enum Op {
     NoOp,
     AddX(isize)
 }
 
+// Puzzle programs: one `noop` or `addx` per line, read by the assembler so mistakes
+// come back with their line number.
 #[aoc_generator(day10)]
-pub fn generator(raw_input: &str) -> Vec<Op> {
+pub fn generator(raw_input: &str) -> Result<Vec<Op>, AssemblyError> {
     raw_input.split("\n")
-        .into_iter()
-        .map(|line| {
-            let split_line = line.split(" ").collect_vec();
-            let op_type = split_line[0];
-            match op_type {
-                "noop" => NoOp,
-                "addx" => {
-                    AddX(split_line[1].parse().unwrap())
+        .enumerate()
+        .map(|(i, line)| {
+            let error = |message: String| AssemblyError { line: i + 1, message };
+            match assemble(line).map_err(|e| error(e.message))?.as_slice() {
+                [Instruction { operation: Operation::Noop, .. }] => Ok(NoOp),
+                [Instruction { operation: Operation::Add, register: 0, value, .. }] => Ok(AddX(*value)),
+                _ => Err(error(format!("'{}' is not a puzzle instruction", line.trim())))
+            }
+        }).collect()
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NoOp => write!(f, "noop"),
+            AddX(v) => write!(f, "addx {}", v)
+        }
+    }
+}
+
+impl Op {
+    // Clock ticks the op takes to complete.
+    pub fn cycles(&self) -> usize {
+        self.instruction().cycles
+    }
+
+    // The same op in the extended instruction set.
+    pub fn instruction(&self) -> Instruction {
+        match self {
+            NoOp => Instruction::new(&INSTRUCTION_SET["noop"], 0, 0),
+            AddX(v) => Instruction::new(&INSTRUCTION_SET["addx"], 0, *v)
+        }
+    }
+}
+
+// Registers of the extended machine. X is the puzzle's and starts at 1; the rest
+// start at 0.
+pub const REGISTERS: [char; 4] = ['x', 'y', 'z', 'w'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Noop,
+    Add,
+    Sub,
+    Mul,
+    Jmp,
+    Jnz,
+}
+
+// What follows a mnemonic in the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operands {
+    None,
+    Value,
+    Target,
+    RegisterTarget,
+}
+
+// Base operations, whether there is one per register (named by suffixing the register,
+// as in `addx` or `muly`), their operands and how many ticks they take.
+const OPERATIONS: [(&str, Operation, bool, Operands, usize); 6] = [
+    ("noop", Operation::Noop, false, Operands::None, 1),
+    ("add", Operation::Add, true, Operands::Value, 2),
+    ("sub", Operation::Sub, true, Operands::Value, 2),
+    ("mul", Operation::Mul, true, Operands::Value, 3),
+    ("jmp", Operation::Jmp, false, Operands::Target, 1),
+    ("jnz", Operation::Jnz, false, Operands::RegisterTarget, 2),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opcode {
+    pub mnemonic: String,
+    pub operation: Operation,
+    // The register a per-register operation works on.
+    pub register: usize,
+    operands: Operands,
+    pub cycles: usize,
+}
+
+lazy_static! {
+    // Every mnemonic the assembler understands.
+    static ref INSTRUCTION_SET: HashMap<String, Opcode> = OPERATIONS.iter()
+        .flat_map(|&(name, operation, per_register, operands, cycles)| {
+            let registers = if per_register { REGISTERS.iter().map(|r| Some(*r)).collect_vec() } else { vec![None] };
+            registers.into_iter().enumerate().map(move |(register, suffix)| {
+                let mnemonic = suffix.map_or(name.to_string(), |r| format!("{}{}", name, r));
+                (mnemonic.clone(), Opcode { mnemonic, operation, register, operands, cycles })
+            })
+        })
+        .collect();
+}
+
+// An assembled instruction. `value` is the operand for arithmetic and the absolute
+// index jumped to for `jmp` and `jnz`; `jnz` tests `register`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub operation: Operation,
+    pub register: usize,
+    pub value: isize,
+    pub cycles: usize,
+}
+
+impl Instruction {
+    fn new(opcode: &Opcode, register: usize, value: isize) -> Instruction {
+        let register = if opcode.operands == Operands::RegisterTarget { register } else { opcode.register };
+        Instruction { operation: opcode.operation, register, value, cycles: opcode.cycles }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssemblyError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AssemblyError {}
+
+// Assembles a program for the extended machine. Each line holds an instruction, a
+// `label:`, or a label followed by an instruction. Jump targets are either a label or
+// a signed offset from the jump itself; jumping just past the last instruction halts.
+// Puzzle programs assemble unchanged.
+pub fn assemble(source: &str) -> Result<Vec<Instruction>, AssemblyError> {
+    let error = |line: usize, message: String| AssemblyError { line: line + 1, message };
+    let mut labels: HashMap<&str, (usize, usize)> = HashMap::new();
+    let mut statements: Vec<(usize, Vec<&str>)> = Vec::new();
+    for (line, text) in source.lines().enumerate() {
+        let mut text = text.trim();
+        if let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            if label.is_empty() || !label.chars().all(|c| c.is_alphanumeric() || c == '_')
+                || label.parse::<isize>().is_ok() {
+                return Err(error(line, format!("invalid label '{}'", label)));
+            }
+            if let Some((first, _)) = labels.insert(label, (statements.len(), line)) {
+                return Err(error(line, format!("label '{}' already defined on line {}", label, first + 1)));
+            }
+            text = rest.trim();
+        }
+        if !text.is_empty() {
+            statements.push((line, text.split_whitespace().collect_vec()));
+        }
+    }
+    let labels: HashMap<&str, usize> = labels.into_iter().map(|(label, (index, _))| (label, index)).collect();
+
+    statements.iter().enumerate().map(|(index, (line, words))| {
+        let opcode = INSTRUCTION_SET.get(words[0])
+            .ok_or_else(|| error(*line, format!("unknown instruction '{}'", words[0])))?;
+        let expected = match opcode.operands {
+            Operands::None => 0,
+            Operands::Value | Operands::Target => 1,
+            Operands::RegisterTarget => 2,
+        };
+        if words.len() != expected + 1 {
+            return Err(error(*line, format!("{} takes {} operand(s), found {}", words[0], expected, words.len() - 1)));
+        }
+        let number = |word: &str| word.parse::<isize>().map_err(|_| error(*line, format!("not a number: '{}'", word)));
+        let target = |word: &str| {
+            let target = match labels.get(word) {
+                Some(&target) => target as isize,
+                None if word.parse::<isize>().is_ok() => index as isize + number(word)?,
+                None => return Err(error(*line, format!("undefined label '{}'", word))),
+            };
+            if target < 0 || target > statements.len() as isize {
+                return Err(error(*line, format!("jump to {} is outside the program", word)));
+            }
+            Ok(target)
+        };
+        let (register, value) = match opcode.operands {
+            Operands::None => (0, 0),
+            Operands::Value => (0, number(words[1])?),
+            Operands::Target => (0, target(words[1])?),
+            Operands::RegisterTarget => {
+                let register = REGISTERS.iter().position(|r| words[1] == r.to_string())
+                    .ok_or_else(|| error(*line, format!("unknown register '{}'", words[1])))?;
+                (register, target(words[2])?)
+            }
+        };
+        Ok(Instruction::new(opcode, register, value))
+    }).collect()
+}
+
+// An arithmetic instruction whose result didn't fit in a register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overflow {
+    pub instruction: usize,
+    pub cycle: isize,
+    pub register: char,
+}
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "instruction {} overflowed register {} on cycle {}", self.instruction, self.register, self.cycle)
+    }
+}
+
+impl Error for Overflow {}
+
+// Runs an assembled program one tick at a time like `Cpu`, yielding each cycle's
+// number with every register's value during it. Programs that loop forever give an
+// endless iterator. Arithmetic is checked: an overflowing instruction halts the
+// machine after its last tick, with the details left in `fault`.
+pub struct Machine {
+    program: Vec<Instruction>,
+    pc: usize,
+    current: Option<usize>,
+    remaining: usize,
+    cycle: isize,
+    registers: [isize; REGISTERS.len()],
+    fault: Option<Overflow>,
+}
+
+impl Machine {
+    pub fn new(program: Vec<Instruction>) -> Machine {
+        let mut registers = [0; REGISTERS.len()];
+        registers[0] = 1;
+        Machine { program, pc: 0, current: None, remaining: 0, cycle: 0, registers, fault: None }
+    }
+
+    // Why the machine halted early, if it did.
+    pub fn fault(&self) -> Option<Overflow> {
+        self.fault
+    }
+
+    // Registers as they stand after the ticks yielded so far.
+    pub fn registers(&self) -> [isize; REGISTERS.len()] {
+        self.registers
+    }
+
+    // Index of the instruction that ran during the last tick yielded.
+    pub fn instruction_index(&self) -> Option<usize> {
+        self.current
+    }
+
+    // Whether the last tick yielded was the first of its instruction.
+    pub fn starting_instruction(&self) -> bool {
+        self.current.is_some_and(|i| self.remaining + 1 == self.program[i].cycles)
+    }
+
+    fn execute(&mut self, instruction: Instruction) {
+        let register = instruction.register;
+        let (current, value) = (self.registers[register], instruction.value);
+        let result = match instruction.operation {
+            Operation::Noop => return,
+            Operation::Add => current.checked_add(value),
+            Operation::Sub => current.checked_sub(value),
+            Operation::Mul => current.checked_mul(value),
+            Operation::Jmp => {
+                self.pc = value as usize;
+                return;
+            }
+            Operation::Jnz => {
+                if current != 0 {
+                    self.pc = value as usize;
                 }
-                _ => panic!("Invalid op type: {}", op_type)
+                return;
             }
-        }).collect_vec()
+        };
+        match result {
+            Some(result) => self.registers[register] = result,
+            None => {
+                self.fault = Some(Overflow {
+                    instruction: self.current.unwrap(),
+                    cycle: self.cycle,
+                    register: REGISTERS[register],
+                });
+            }
+        }
+    }
+}
+
+impl Iterator for Machine {
+    type Item = (isize, [isize; REGISTERS.len()]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.fault.is_some() {
+            return None;
+        }
+        if self.remaining == 0 {
+            self.remaining = self.program.get(self.pc)?.cycles;
+            self.current = Some(self.pc);
+            self.pc += 1;
+        }
+        self.cycle += 1;
+        let tick = (self.cycle, self.registers);
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            self.execute(self.program[self.current.unwrap()]);
+        }
+        Some(tick)
+    }
+}
+
+// Runs a program one clock tick at a time, yielding each cycle's number (counting from
+// 1) with the value X holds during it. An op's effect lands once its last tick is over,
+// so an `addx` only shows up in the cycle after it.
+pub struct Cpu<'a> {
+    ops: &'a [Op],
+    machine: Machine,
+}
+
+impl<'a> Cpu<'a> {
+    pub fn new(ops: &'a [Op]) -> Cpu<'a> {
+        Cpu { ops, machine: Machine::new(ops.iter().map(Op::instruction).collect_vec()) }
+    }
+
+    // X as it stands after the ticks yielded so far.
+    pub fn x(&self) -> isize {
+        self.machine.registers()[0]
+    }
+
+    pub fn fault(&self) -> Option<Overflow> {
+        self.machine.fault()
+    }
+
+    // The index and op that ran during the last tick yielded.
+    pub fn instruction(&self) -> Option<(usize, &'a Op)> {
+        let index = self.machine.instruction_index()?;
+        Some((index, &self.ops[index]))
+    }
 }
 
-fn perform_op(op: &Op, current_cycle: isize, current_x: isize) -> (isize, isize) {
-    match op {
-        NoOp => (current_cycle + 1, current_x),
-        AddX(v) => (current_cycle + 2, current_x + v)
+impl Iterator for Cpu<'_> {
+    type Item = (isize, isize);
+
+    fn next(&mut self) -> Option<(isize, isize)> {
+        self.machine.next().map(|(cycle, registers)| (cycle, registers[0]))
     }
 }
 
 #[aoc(day10, part1)]
 pub fn solve_part1(ops: &[Op]) -> isize {
-    let mut current_cycle = 1;
-    let mut current_reg = 1;
-    let pois: Vec<isize> = vec![
-        20,
-        60,
-        100,
-        140,
-        180,
-        220
+    Cpu::new(ops)
+        .take_while(|(cycle, _)| *cycle <= 220)
+        .filter(|(cycle, _)| cycle % 40 == 20)
+        .map(|(cycle, x)| cycle * x)
+        .sum()
+}
+
+#[aoc(day10, part2)]
+pub fn solve_part2(ops: &[Op]) -> String {
+    format!("\n{}", render_crt(ops))
+}
+
+#[aoc(day10, part2, ocr)]
+pub fn solve_part2_ocr(ops: &[Op]) -> Result<String, UnknownGlyph> {
+    decode_crt(&render_crt(ops))
+}
+
+// The raw 6x40 screen, one line per row.
+pub fn render_crt(ops: &[Op]) -> String {
+    let mut crt: [[char; 40]; 6] = [['.'; 40];6];
+    for (cycle, x) in Cpu::new(ops).take(240) {
+        crt[((cycle - 1) / 40) as usize][((cycle - 1) % 40) as usize] = crt_pixel(cycle, x);
+    }
+    crt.iter().map(|row| { row.iter().join("") }).join("\n")
+}
+
+// What the beam draws during `cycle`: lit if the three-pixel sprite centred on `x`
+// covers the beam's position along its row.
+fn crt_pixel(cycle: isize, x: isize) -> char {
+    if ((cycle - 1) % 40 - x).abs() <= 1 { '#' } else { '.' }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl Comparison {
+    const SYMBOLS: [(&'static str, Comparison); 6] = [
+        ("<=", Comparison::Le),
+        (">=", Comparison::Ge),
+        ("==", Comparison::Eq),
+        ("!=", Comparison::Ne),
+        ("<", Comparison::Lt),
+        (">", Comparison::Gt),
     ];
-    let mut poinum = 0;
-    let mut signal_strength_sum = 0;
-    for op in ops {
-        let prev_reg = current_reg;
-        (current_cycle, current_reg) = perform_op(op, current_cycle, current_reg);
-        if current_cycle > pois[poinum] {
-            signal_strength_sum += pois[poinum] * prev_reg;
-            poinum += 1;
+
+    pub fn holds(&self, left: isize, right: isize) -> bool {
+        match self {
+            Comparison::Lt => left < right,
+            Comparison::Le => left <= right,
+            Comparison::Gt => left > right,
+            Comparison::Ge => left >= right,
+            Comparison::Eq => left == right,
+            Comparison::Ne => left != right,
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        Self::SYMBOLS.iter().find(|(_, c)| c == self).unwrap().0
+    }
+}
+
+// Where the debugger stops: on reaching a cycle, on starting the op at an index (from
+// 0) into the program, or on a tick where X comes to satisfy a comparison it didn't
+// on the tick before, so `continue` runs on while the condition keeps holding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    Cycle(isize),
+    Instruction(usize),
+    Register(Comparison, isize),
+}
+
+impl Breakpoint {
+    fn hit(&self, cycle: isize, x: isize, previous_x: Option<isize>, instruction: usize, starting: bool) -> bool {
+        match self {
+            Breakpoint::Cycle(c) => cycle == *c,
+            Breakpoint::Instruction(i) => starting && instruction == *i,
+            Breakpoint::Register(comparison, value) => {
+                comparison.holds(x, *value) && !previous_x.is_some_and(|previous| comparison.holds(previous, *value))
+            }
         }
-        if poinum >= pois.len() {
-            break;
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Breakpoint::Cycle(c) => write!(f, "cycle {}", c),
+            Breakpoint::Instruction(i) => write!(f, "instruction {}", i),
+            Breakpoint::Register(comparison, value) => write!(f, "x {} {}", comparison.symbol(), value),
         }
     }
-    signal_strength_sum
 }
 
-#[aoc(day10, part2)]
-pub fn solve_part2(ops: &[Op]) -> String {
-    let mut crt: [[char; 40]; 6] = [['.'; 40];6];
-    let mut current_cycle = 0;
-    let mut current_reg = 1;
-    for op in ops {
-        let prev_reg = current_reg;
-        let prev_cycle = current_cycle;
-        (current_cycle, current_reg) = perform_op(op, current_cycle, current_reg);
-        for i in prev_cycle..current_cycle {
-            let row_pos = mod_floor(i, 40);
-            if (row_pos -1 <= prev_reg) & (row_pos +1 >= prev_reg) {
-                crt[(i/40) as usize][row_pos as usize] = '#';
+// `cycle 20`, `instruction 3` or a condition on X such as `x < 0` or `x==5`.
+impl FromStr for Breakpoint {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Breakpoint, String> {
+        let spec = spec.trim();
+        let number = |text: &str| text.trim().parse().map_err(|_| format!("not a number: '{}'", text.trim()));
+        if let Some(cycle) = spec.strip_prefix("cycle ") {
+            return Ok(Breakpoint::Cycle(number(cycle)?));
+        }
+        if let Some(index) = spec.strip_prefix("instruction ") {
+            return index.trim().parse().map(Breakpoint::Instruction)
+                .map_err(|_| format!("not an instruction index: '{}'", index.trim()));
+        }
+        if let Some(condition) = spec.strip_prefix('x') {
+            let condition = condition.trim_start();
+            for (symbol, comparison) in Comparison::SYMBOLS {
+                if let Some(value) = condition.strip_prefix(symbol) {
+                    return Ok(Breakpoint::Register(comparison, number(value)?));
+                }
+            }
+        }
+        Err(format!("unknown breakpoint '{}', expected 'cycle N', 'instruction N' or 'x OP N'", spec))
+    }
+}
+
+// A program paused between clock ticks, with the screen as drawn so far.
+pub struct Debugger<'a> {
+    cpu: Cpu<'a>,
+    tick: Option<(isize, isize)>,
+    pixels: Vec<char>,
+    pub breakpoints: Vec<Breakpoint>,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(ops: &'a [Op]) -> Debugger<'a> {
+        Debugger { cpu: Cpu::new(ops), tick: None, pixels: Vec::new(), breakpoints: Vec::new() }
+    }
+
+    // Runs one tick, returning false once the program has finished.
+    pub fn step(&mut self) -> bool {
+        match self.cpu.next() {
+            Some((cycle, x)) => {
+                self.tick = Some((cycle, x));
+                self.pixels.push(crt_pixel(cycle, x));
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Runs ticks until one trips a breakpoint, which is returned, or the program ends.
+    pub fn run(&mut self) -> Option<Breakpoint> {
+        loop {
+            let previous_x = self.tick.map(|(_, x)| x);
+            if !self.step() {
+                return None;
+            }
+            let (cycle, x) = self.tick.unwrap();
+            let (index, _) = self.cpu.instruction().unwrap();
+            let starting = self.cpu.machine.starting_instruction();
+            if let Some(breakpoint) = self.breakpoints.iter().find(|b| b.hit(cycle, x, previous_x, index, starting)) {
+                return Some(*breakpoint);
+            }
+        }
+    }
+
+    // The CRT rows drawn so far, the last one possibly partial.
+    pub fn screen(&self) -> String {
+        self.pixels.chunks(40).map(|row| row.iter().collect::<String>()).join("\n")
+    }
+
+    // How the program stopped, once `step` or `run` has run out of ticks.
+    pub fn ending(&self) -> String {
+        match self.cpu.fault() {
+            Some(overflow) => format!("program halted: {}", overflow),
+            None => format!("program finished with x = {}", self.cpu.x()),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match (self.tick, self.cpu.instruction()) {
+            (Some((cycle, x)), Some((index, op))) => {
+                format!("cycle {}, x = {}, instruction {}: {}", cycle, x, index, op)
+            }
+            _ => "not started".to_string(),
+        }
+    }
+}
+
+const DEBUGGER_HELP: &str = "\
+commands:
+  step [N]            run one tick, or N
+  continue            run until a breakpoint or the end of the program
+  break SPEC          stop at 'cycle N', 'instruction N' or 'x OP N' (OP: < <= > >= == !=)
+  delete N            remove breakpoint N
+  breakpoints         list breakpoints
+  print               show cycle, x, instruction and the screen so far
+  quit";
+
+// Drives a `Debugger` over `ops` from commands read off `input`, one per line, writing
+// a prompt and the results of each to `output`. Commands can be shortened to their
+// first letter.
+pub fn debug<R: BufRead, W: Write>(ops: &[Op], input: R, mut output: W) -> io::Result<()> {
+    let mut debugger = Debugger::new(ops);
+    write!(output, "(day10) ")?;
+    output.flush()?;
+    for line in input.lines() {
+        let line = line?;
+        let (command, args) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        match command {
+            "" => {}
+            "step" | "s" => {
+                let count = if args.is_empty() { Ok(1) } else { args.trim().parse::<usize>() };
+                match count {
+                    Ok(count) => {
+                        if (0..count).all(|_| debugger.step()) {
+                            writeln!(output, "{}", debugger.describe())?;
+                        } else {
+                            writeln!(output, "{}", debugger.ending())?;
+                        }
+                    }
+                    Err(_) => writeln!(output, "not a step count: '{}'", args.trim())?,
+                }
+            }
+            "continue" | "c" => match debugger.run() {
+                Some(breakpoint) => {
+                    writeln!(output, "stopped at {}", breakpoint)?;
+                    writeln!(output, "{}", debugger.describe())?;
+                }
+                None => writeln!(output, "{}", debugger.ending())?,
+            },
+            "break" | "b" => match args.parse::<Breakpoint>() {
+                Ok(breakpoint) => {
+                    debugger.breakpoints.push(breakpoint);
+                    writeln!(output, "breakpoint {}: {}", debugger.breakpoints.len() - 1, breakpoint)?;
+                }
+                Err(message) => writeln!(output, "{}", message)?,
+            },
+            "delete" | "d" => match args.trim().parse::<usize>() {
+                Ok(n) if n < debugger.breakpoints.len() => {
+                    writeln!(output, "deleted breakpoint {}: {}", n, debugger.breakpoints.remove(n))?;
+                }
+                _ => writeln!(output, "no breakpoint '{}'", args.trim())?,
+            },
+            "breakpoints" => {
+                for (n, breakpoint) in debugger.breakpoints.iter().enumerate() {
+                    writeln!(output, "{}: {}", n, breakpoint)?;
+                }
             }
+            "print" | "p" => {
+                writeln!(output, "{}", debugger.describe())?;
+                writeln!(output, "{}", debugger.screen())?;
+            }
+            "quit" | "q" => return Ok(()),
+            "help" | "h" => writeln!(output, "{}", DEBUGGER_HELP)?,
+            _ => writeln!(output, "unknown command '{}', try 'help'", command)?,
         }
+        write!(output, "(day10) ")?;
+        output.flush()?;
+    }
+    Ok(())
+}
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownGlyph {
+    // 0-based position of the letter along the screen.
+    pub position: usize,
+    pub glyph: String,
+}
+
+impl fmt::Display for UnknownGlyph {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unrecognised glyph at letter {}:\n{}", self.position, self.glyph)
     }
-    let readout = crt.iter().map(|row| { row.iter().join("") }).join("\n");
-    format!("\n{}", readout)
+}
+
+impl Error for UnknownGlyph {}
+
+// Reads the letters off a screen drawn by `render_crt`. Letters are four pixels wide
+// with a blank column after each; surrounding blank lines are ignored.
+pub fn decode_crt(screen: &str) -> Result<String, UnknownGlyph> {
+    let rows = screen.trim_matches('\n').lines().map(|row| row.chars().collect_vec()).collect_vec();
+    let width = rows.first().map_or(0, Vec::len);
+    (0..width).step_by(5).enumerate().map(|(position, left)| {
+        let glyph = rows.iter()
+            .map(|row| row.iter().skip(left).take(4).collect::<String>())
+            .join("\n");
+        FONT.get(&glyph).copied().ok_or(UnknownGlyph { position, glyph })
+    }).collect()
 }
 
 #[cfg(test)]
@@ -252,18 +841,159 @@ noop";
             AddX(3),
             AddX(-5)
         ];
-        assert_eq!(generator(&EXAMPLE), expected);
+        assert_eq!(generator(&EXAMPLE), Ok(expected));
+    }
+
+    #[test]
+    fn test_generator_errors() {
+        assert_eq!(generator("noop\naddx 1\nsubx 2").unwrap_err().to_string(), "line 3: 'subx 2' is not a puzzle instruction");
+        assert_eq!(generator("noop\naddx").unwrap_err().to_string(), "line 2: addx takes 1 operand(s), found 0");
+        assert_eq!(generator("nop").unwrap_err().to_string(), "line 1: unknown instruction 'nop'");
+    }
+
+    #[test]
+    fn test_cpu() {
+        let example: Vec<Op> = generator(EXAMPLE).unwrap();
+        let mut cpu = Cpu::new(&example);
+        assert_eq!(cpu.by_ref().collect_vec(), vec![(1, 1), (2, 1), (3, 1), (4, 4), (5, 4)]);
+        assert_eq!(cpu.x(), -1);
+
+        let example: Vec<Op> = generator(EXAMPLE2).unwrap();
+        let ticks = Cpu::new(&example).collect_vec();
+        assert_eq!(ticks.len(), 240);
+        assert_eq!(ticks[19], (20, 21));
+        assert_eq!(ticks[219], (220, 18));
+    }
+
+    #[test]
+    fn test_breakpoint_from_str() {
+        assert_eq!("cycle 20".parse(), Ok(Breakpoint::Cycle(20)));
+        assert_eq!("instruction 3".parse(), Ok(Breakpoint::Instruction(3)));
+        assert_eq!("x < 0".parse(), Ok(Breakpoint::Register(Comparison::Lt, 0)));
+        assert_eq!("x<=-5".parse(), Ok(Breakpoint::Register(Comparison::Le, -5)));
+        assert_eq!(" x != 2 ".parse(), Ok(Breakpoint::Register(Comparison::Ne, 2)));
+        assert!("x ~ 2".parse::<Breakpoint>().is_err());
+        assert!("cycle soon".parse::<Breakpoint>().unwrap_err().contains("soon"));
+        assert_eq!(Breakpoint::Register(Comparison::Ge, 3).to_string(), "x >= 3");
+    }
+
+    #[test]
+    fn test_debug() {
+        let example: Vec<Op> = generator(EXAMPLE2).unwrap();
+        let commands = "break cycle 20\nc\nb x < 0\nc\nbreakpoints\nd 0\nb instruction 130\ns 2\nc\nc\np\nwhat\nq\nstep";
+        let mut output = Vec::new();
+        debug(&example, commands.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let expected = "\
+(day10) breakpoint 0: cycle 20
+(day10) stopped at cycle 20
+cycle 20, x = 21, instruction 10: addx -1
+(day10) breakpoint 1: x < 0
+(day10) stopped at x < 0
+cycle 210, x = -1, instruction 127: addx 22
+(day10) 0: cycle 20
+1: x < 0
+(day10) deleted breakpoint 0: cycle 20
+(day10) breakpoint 1: instruction 130
+(day10) cycle 212, x = 21, instruction 128: addx -6
+(day10) stopped at instruction 130
+cycle 216, x = 16, instruction 130: noop
+(day10) program finished with x = 17
+(day10) cycle 240, x = 17, instruction 145: noop
+PART2
+(day10) unknown command 'what', try 'help'
+(day10) ";
+        assert_eq!(output, expected.replace("\nPART2", PART2_SOLN));
+
+        // A register breakpoint stops where X crosses into the condition, not on every
+        // tick it keeps holding.
+        let program = generator("addx -3\nnoop\nnoop\naddx 5\nnoop\naddx -5\nnoop").unwrap();
+        let mut output = Vec::new();
+        debug(&program, "b x < 0\nc\nc\nc".as_bytes(), &mut output).unwrap();
+        let expected = "\
+(day10) breakpoint 0: x < 0
+(day10) stopped at x < 0
+cycle 3, x = -2, instruction 1: noop
+(day10) stopped at x < 0
+cycle 10, x = -2, instruction 6: noop
+(day10) program finished with x = -2
+(day10) ";
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_assemble() {
+        // Puzzle programs run on the extended machine exactly as on `Cpu`.
+        let example: Vec<Op> = generator(EXAMPLE2).unwrap();
+        let machine = Machine::new(assemble(EXAMPLE2).unwrap());
+        assert!(machine.map(|(cycle, registers)| (cycle, registers[0])).eq(Cpu::new(&example)));
+
+        let program = "\
+addy 5
+loop:
+  addx 2
+  suby 1
+  jnz y loop
+done: mulx 3";
+        let ticks = Machine::new(assemble(program).unwrap()).collect_vec();
+        assert_eq!(ticks.len(), 2 + 5 * 6 + 3);
+        let mut machine = Machine::new(assemble(program).unwrap());
+        machine.by_ref().for_each(drop);
+        assert_eq!(machine.registers(), [33, 0, 0, 0]);
+
+        // Skips the addx, then counts z down forever.
+        let mut machine = Machine::new(assemble("jmp 2\naddx 100\nsubz 4\njnz z -1").unwrap());
+        let ticks = machine.by_ref().take(1 + 4 * 10).collect_vec();
+        assert_eq!(ticks[1], (2, [1, 0, 0, 0]));
+        assert_eq!(ticks[3], (4, [1, 0, -4, 0]));
+        assert_eq!(machine.registers(), [1, 0, -40, 0]);
+        assert_eq!(machine.next(), Some((42, [1, 0, -40, 0])));
+
+        // Overflow halts the machine instead of wrapping or panicking.
+        let mut machine = Machine::new(assemble("loop: mulx 2\njmp loop").unwrap());
+        let ticks = machine.by_ref().count();
+        assert_eq!(machine.registers()[0], 1 << 62);
+        assert_eq!(ticks, 4 * 62 + 3);
+        assert_eq!(machine.fault(), Some(Overflow { instruction: 0, cycle: 251, register: 'x' }));
+        assert_eq!(machine.fault().unwrap().to_string(), "instruction 0 overflowed register x on cycle 251");
+
+        let error = |source: &str| assemble(source).unwrap_err().to_string();
+        assert_eq!(error("noop\naddq 1"), "line 2: unknown instruction 'addq'");
+        assert_eq!(error("addx"), "line 1: addx takes 1 operand(s), found 0");
+        assert_eq!(error("noop\n\nmulw two"), "line 3: not a number: 'two'");
+        assert_eq!(error("jmp nowhere"), "line 1: undefined label 'nowhere'");
+        assert_eq!(error("a:\nnoop\na: noop"), "line 3: label 'a' already defined on line 1");
+        assert_eq!(error("jnz q 0"), "line 1: unknown register 'q'");
+        assert_eq!(error("noop\njmp -2"), "line 2: jump to -2 is outside the program");
+        assert_eq!(error("1: noop"), "line 1: invalid label '1'");
     }
 
     #[test]
     fn test_solve_part1() {
-        let example: Vec<Op> = generator(&EXAMPLE2);
+        let example: Vec<Op> = generator(&EXAMPLE2).unwrap();
         assert_eq!(solve_part1(&example), 13140);
     }
 
+    #[test]
+    fn test_decode_crt() {
+        let screen = "\
+####.#..#.####.####.####.#..#..##..####
+#....#..#....#.#.......#.#..#.#..#....#
+###..####...#..###....#..####.#......#.
+#....#..#..#...#.....#...#..#.#.....#..
+#....#..#.#....#....#....#..#.#..#.#...
+####.#..#.####.#....####.#..#..##..####";
+        assert_eq!(decode_crt(screen), Ok("EHZFZHCZ".to_string()));
+
+        let error = decode_crt(PART2_SOLN).unwrap_err();
+        assert_eq!(error.position, 0);
+        assert_eq!(error.glyph, "##..\n###.\n####\n####\n####\n####");
+        assert!(error.to_string().starts_with("unrecognised glyph at letter 0:\n##.."));
+    }
+
     #[test]
     fn test_solve_part2() {
-        let example: Vec<Op> = generator(&EXAMPLE2);
+        let example: Vec<Op> = generator(&EXAMPLE2).unwrap();
         assert_eq!(solve_part2(&example), PART2_SOLN);
     }
 }
\ No newline at end of file
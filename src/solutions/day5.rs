@@ -1,11 +1,14 @@
-use std::collections::LinkedList;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, LinkedList};
 
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
 
-#[aoc_generator(day5)]
-pub fn generator(raw_input: &str) -> (Vec<LinkedList<char>>, Vec<(usize, usize, usize)>) {
-    let (state, steps): (&str, &str) = raw_input.split("\n\n").collect_tuple().unwrap();
+use crate::solutions::rng::Lcg;
+
+pub type Drawing = (Vec<LinkedList<char>>, Vec<(usize, usize, usize)>);
+
+pub fn parse_stacks(state: &str) -> Vec<LinkedList<char>> {
     let stack_count: usize = state.split("\n")
         .map(|s| s.len() + 1)
         .max()
@@ -25,7 +28,28 @@ pub fn generator(raw_input: &str) -> (Vec<LinkedList<char>>, Vec<(usize, usize,
             i += 4
         }
     });
+    stacks
+}
+
+pub fn render_stacks(stacks: &[LinkedList<char>]) -> String {
+    let height = stacks.iter().map(|stack| stack.len()).max().unwrap_or(0);
+    let mut rows = (0..height).rev().map(|level| {
+        stacks.iter().map(|stack| {
+            match stack.iter().nth(level) {
+                Some(name) => format!("[{}]", name),
+                None => String::from("   ")
+            }
+        }).join(" ")
+    }).collect_vec();
+    rows.push((1..=stacks.len()).map(|i| format!(" {} ", i)).join(" "));
+    rows.join("\n")
+}
+
+#[aoc_generator(day5)]
+pub fn generator(raw_input: &str) -> Drawing {
+    let (state, steps): (&str, &str) = raw_input.split("\n\n").collect_tuple().unwrap();
     let procedure = steps.split("\n")
+        .filter(|proc| !proc.is_empty())
         .map(|proc| {
             proc.split(" ")
                 .filter(|s| s.chars().all(char::is_numeric))
@@ -33,45 +57,229 @@ pub fn generator(raw_input: &str) -> (Vec<LinkedList<char>>, Vec<(usize, usize,
                 .collect_tuple()
                 .unwrap()
         }).collect_vec();
-    (stacks, procedure)
+    (parse_stacks(state), procedure)
 }
 
-#[aoc(day5, part1)]
-pub fn solve_part1(input: &(Vec<LinkedList<char>>, Vec<(usize, usize, usize)>)) -> String {
-    let (mut state, procedure) = input.clone();
-    procedure.into_iter().for_each(|(count, from, to)| {
-        for _ in 0..count {
-            let crate_name = state.get_mut(from - 1)
-                .unwrap()
-                .pop_back()
-                .unwrap();
-            state.get_mut(to - 1)
-                .as_mut()
-                .unwrap()
-                .push_back(crate_name);
+pub fn apply_procedure(
+    stacks: &[LinkedList<char>],
+    procedure: &[(usize, usize, usize)],
+    crane: CraneModel,
+) -> Vec<LinkedList<char>> {
+    let mut state = stacks.to_vec();
+    procedure.iter().for_each(|&(count, from, to)| {
+        match crane {
+            CraneModel::CrateMover9000 => {
+                for _ in 0..count {
+                    let crate_name = state.get_mut(from - 1)
+                        .unwrap()
+                        .pop_back()
+                        .unwrap();
+                    state.get_mut(to - 1)
+                        .unwrap()
+                        .push_back(crate_name);
+                }
+            }
+            CraneModel::CrateMover9001 => {
+                let stack = state.get_mut(from - 1)
+                    .unwrap();
+                let mut moved = stack.split_off(stack.len() - count);
+                state.get_mut(to - 1)
+                    .unwrap()
+                    .append(&mut moved);
+            }
         }
     });
-    state.into_iter().map(|list| list.back().unwrap().clone()).join("")
+    state
+}
+
+#[aoc(day5, part1)]
+pub fn solve_part1(input: &Drawing) -> String {
+    let (state, procedure) = input;
+    apply_procedure(state, procedure, CraneModel::CrateMover9000).into_iter()
+        .map(|list| *list.back().unwrap())
+        .join("")
 }
 
 #[aoc(day5, part2)]
-pub fn solve_part2(input: &(Vec<LinkedList<char>>, Vec<(usize, usize, usize)>)) -> String {
-    let (mut state, procedure) = input.clone();
-    procedure.into_iter().for_each(|(count, from, to)| {
-        let stack = state.get_mut(from - 1)
-            .unwrap();
-        let mut moved = stack.split_off(stack.len() - count);
-        state.get_mut(to - 1)
-            .as_mut()
-            .unwrap()
-            .append(&mut moved);
+pub fn solve_part2(input: &Drawing) -> String {
+    let (state, procedure) = input;
+    apply_procedure(state, procedure, CraneModel::CrateMover9001).into_iter()
+        .map(|list| *list.back().unwrap())
+        .join("")
+}
+
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum CraneModel {
+    CrateMover9000,
+    CrateMover9001,
+}
+
+// Follows each final top crate backwards through the procedure to its starting
+// slot, so only the crates that end up on top are ever looked at.
+pub fn backtrack_tops(input: &Drawing, crane: CraneModel) -> String {
+    let (stacks, procedure) = input;
+    let mut heights = stacks.iter().map(|stack| stack.len()).collect_vec();
+    for &(count, from, to) in procedure {
+        heights[from - 1] -= count;
+        heights[to - 1] += count;
+    }
+    let initial = stacks.iter().map(|stack| stack.iter().copied().collect_vec()).collect_vec();
+    (0..stacks.len())
+        .filter(|&stack| heights[stack] > 0)
+        .map(|stack| {
+            let (mut stack, mut depth) = (stack, 0);
+            for &(count, from, to) in procedure.iter().rev() {
+                if stack == to - 1 {
+                    if depth < count {
+                        stack = from - 1;
+                        if crane == CraneModel::CrateMover9000 {
+                            depth = count - 1 - depth;
+                        }
+                    } else {
+                        depth -= count;
+                    }
+                } else if stack == from - 1 {
+                    depth += count;
+                }
+            }
+            let column = &initial[stack];
+            column[column.len() - 1 - depth]
+        })
+        .join("")
+}
+
+#[aoc(day5, part1, backtrack)]
+pub fn solve_part1_backtrack(input: &Drawing) -> String {
+    backtrack_tops(input, CraneModel::CrateMover9000)
+}
+
+#[aoc(day5, part2, backtrack)]
+pub fn solve_part2_backtrack(input: &Drawing) -> String {
+    backtrack_tops(input, CraneModel::CrateMover9001)
+}
+
+// Builds a puzzle input with `stack_count` stacks of `height` crates and `move_count`
+// random moves that never empty a stack. A fixed `seed` always yields the same input.
+pub fn generate_large_input(stack_count: usize, height: usize, move_count: usize, seed: u64) -> String {
+    assert!((2..=9).contains(&stack_count), "The drawing only supports 2 to 9 stacks");
+    assert!(height > 1, "Stacks need at least two crates to move anything");
+    let mut rng = Lcg::new(seed);
+    let mut next = |bound: usize| rng.below(bound);
+    let mut rows = (0..height).map(|_| {
+        (0..stack_count).map(|_| format!("[{}]", (b'A' + next(26) as u8) as char)).join(" ")
+    }).collect_vec();
+    rows.push((1..=stack_count).map(|i| format!(" {} ", i)).join(" "));
+    let mut heights = vec![height; stack_count];
+    let moves = (0..move_count).map(|_| {
+        let from = loop {
+            let candidate = next(stack_count);
+            if heights[candidate] > 1 {
+                break candidate;
+            }
+        };
+        let to = (from + 1 + next(stack_count - 1)) % stack_count;
+        let count = 1 + next(heights[from] - 1);
+        heights[from] -= count;
+        heights[to] += count;
+        format!("move {} from {} to {}", count, from + 1, to + 1)
+    }).collect_vec();
+    format!("{}\n\n{}", rows.join("\n"), moves.join("\n"))
+}
+
+// Lower bound on the moves left: every stack with crates above its matching base
+// needs a move off it, every stack missing crates needs a move onto it, and a
+// single move serves one of each.
+fn moves_lower_bound(state: &[Vec<char>], target: &[Vec<char>]) -> usize {
+    let (mut sources, mut destinations) = (0, 0);
+    state.iter().zip(target).for_each(|(current, wanted)| {
+        let matching = current.iter().zip(wanted).take_while(|(a, b)| a == b).count();
+        if current.len() > matching {
+            sources += 1;
+        }
+        if wanted.len() > matching {
+            destinations += 1;
+        }
     });
-    state.into_iter().map(|list| list.back().unwrap().clone()).join("")
+    sources.max(destinations)
+}
+
+fn crane_move(state: &[Vec<char>], (count, from, to): (usize, usize, usize), crane: CraneModel) -> Vec<Vec<char>> {
+    let mut next = state.to_vec();
+    let split = next[from - 1].len() - count;
+    let mut moved = next[from - 1].split_off(split);
+    if crane == CraneModel::CrateMover9000 {
+        moved.reverse();
+    }
+    next[to - 1].append(&mut moved);
+    next
+}
+
+type PlanStep = (Vec<Vec<char>>, (usize, usize, usize));
+
+// A* search for the shortest procedure turning `start` into `target`. Returns
+// `None` when the crates differ or the target can't be reached with this crane.
+pub fn plan_rearrangement(
+    start: &[LinkedList<char>],
+    target: &[LinkedList<char>],
+    crane: CraneModel,
+) -> Option<Vec<(usize, usize, usize)>> {
+    if start.len() != target.len() {
+        return None;
+    }
+    let start = start.iter().map(|stack| stack.iter().copied().collect_vec()).collect_vec();
+    let target = target.iter().map(|stack| stack.iter().copied().collect_vec()).collect_vec();
+    if start.iter().flat_map(|stack| stack.iter()).sorted() != target.iter().flat_map(|stack| stack.iter()).sorted() {
+        return None;
+    }
+    let mut parents: HashMap<Vec<Vec<char>>, Option<PlanStep>> = HashMap::new();
+    let mut costs: HashMap<Vec<Vec<char>>, usize> = HashMap::from([(start.clone(), 0)]);
+    let mut queue = BinaryHeap::from([Reverse((moves_lower_bound(&start, &target), 0, start.clone()))]);
+    parents.insert(start, None);
+    while let Some(Reverse((_, cost, state))) = queue.pop() {
+        if state == target {
+            let mut procedure = Vec::with_capacity(cost);
+            let mut current = &state;
+            while let Some((parent, step)) = &parents[current] {
+                procedure.push(*step);
+                current = parent;
+            }
+            procedure.reverse();
+            return Some(procedure);
+        }
+        if costs[&state] < cost {
+            continue;
+        }
+        for from in 1..=state.len() {
+            for to in (1..=state.len()).filter(|&to| to != from) {
+                for count in 1..=state[from - 1].len() {
+                    let step = (count, from, to);
+                    let next = crane_move(&state, step, crane);
+                    if costs.get(&next).is_some_and(|&known| known <= cost + 1) {
+                        continue;
+                    }
+                    costs.insert(next.clone(), cost + 1);
+                    parents.insert(next.clone(), Some((state.clone(), step)));
+                    let estimate = cost + 1 + moves_lower_bound(&next, &target);
+                    queue.push(Reverse((estimate, cost + 1, next)));
+                }
+            }
+        }
+    }
+    None
+}
+
+// Writes `start` and the procedure as a puzzle input that `generator` reads back.
+pub fn format_plan(start: &[LinkedList<char>], procedure: &[(usize, usize, usize)]) -> String {
+    let steps = procedure.iter()
+        .map(|(count, from, to)| format!("move {} from {} to {}", count, from, to))
+        .join("\n");
+    format!("{}\n\n{}", render_stacks(start), steps)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::solutions::rng::assert_solvers_agree;
 
     lazy_static! {
         static ref EXAMPLE: String = ["    [D]    ",
@@ -94,4 +302,71 @@ mod tests {
     fn test_solve_part2() {
         assert_eq!(solve_part2(&generator(&EXAMPLE)), "MCD");
     }
+
+    #[test]
+    fn test_backtrack_tops() {
+        let example = generator(&EXAMPLE);
+        assert_eq!(solve_part1_backtrack(&example), "CMZ");
+        assert_eq!(solve_part2_backtrack(&example), "MCD");
+    }
+
+    #[test]
+    fn test_backtrack_tops_large_input() {
+        assert_solvers_agree(
+            0..5,
+            |seed| generator(&generate_large_input(9, 200, 5000, seed)),
+            |input| (solve_part1(input), solve_part2(input)),
+            |input| (solve_part1_backtrack(input), solve_part2_backtrack(input)),
+        );
+    }
+
+    fn shortest_by_bfs(start: &[LinkedList<char>], target: &[LinkedList<char>], crane: CraneModel) -> usize {
+        let start = start.iter().map(|stack| stack.iter().copied().collect_vec()).collect_vec();
+        let target = target.iter().map(|stack| stack.iter().copied().collect_vec()).collect_vec();
+        let mut seen = std::collections::HashSet::from([start.clone()]);
+        let mut frontier = vec![start];
+        let mut depth = 0;
+        while !frontier.contains(&target) {
+            frontier = frontier.iter().flat_map(|state| {
+                (1..=state.len()).cartesian_product(1..=state.len())
+                    .filter(|(from, to)| from != to)
+                    .flat_map(move |(from, to)| {
+                        (1..=state[from - 1].len()).map(move |count| crane_move(state, (count, from, to), crane))
+                    })
+            }).filter(|next| seen.insert(next.clone())).collect_vec();
+            depth += 1;
+        }
+        depth
+    }
+
+    #[test]
+    fn test_render_stacks() {
+        let (stacks, _) = generator(&EXAMPLE);
+        assert_eq!(parse_stacks(&render_stacks(&stacks)), stacks);
+        assert_eq!(render_stacks(&stacks), EXAMPLE.split("\n\n").next().unwrap());
+    }
+
+    #[test]
+    fn test_plan_rearrangement() {
+        let (start, procedure) = generator(&EXAMPLE);
+        for crane in [CraneModel::CrateMover9000, CraneModel::CrateMover9001] {
+            let target = apply_procedure(&start, &procedure, crane);
+            let plan = plan_rearrangement(&start, &target, crane).unwrap();
+            assert_eq!(plan.len(), shortest_by_bfs(&start, &target, crane));
+            assert!(plan.len() <= procedure.len());
+            let (replayed_start, replayed_procedure) = generator(&format_plan(&start, &plan));
+            assert_eq!(apply_procedure(&replayed_start, &replayed_procedure, crane), target);
+        }
+    }
+
+    #[test]
+    fn test_plan_rearrangement_unreachable() {
+        let start = parse_stacks("[A]    \n[B] [C]\n 1   2 ");
+        let target = parse_stacks("[A]    \n[C] [B]\n 1   2 ");
+        assert_eq!(plan_rearrangement(&start, &target, CraneModel::CrateMover9000), None);
+        assert_eq!(plan_rearrangement(&start, &target, CraneModel::CrateMover9001).unwrap().len(), 4);
+        let swapped = parse_stacks("[A]    \n[B] [D]\n 1   2 ");
+        assert_eq!(plan_rearrangement(&start, &swapped, CraneModel::CrateMover9000), None);
+        assert_eq!(plan_rearrangement(&start, &start, CraneModel::CrateMover9000), Some(vec![]));
+    }
 }
\ No newline at end of file
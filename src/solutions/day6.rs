@@ -1,5 +1,8 @@
-use std::collections::{HashMap, LinkedList};
+use std::collections::{HashMap, LinkedList, VecDeque};
+use std::hash::Hash;
+use std::io::{self, Read};
 use aoc_runner_derive::aoc;
+use crate::solutions::rng::Lcg;
 
 fn find_first_unique_sequence(input: &str, sequence_length: usize) -> usize {
     let mut window: LinkedList<char> = LinkedList::new();
@@ -33,9 +36,153 @@ pub fn solve_part2(input: &str) -> usize {
     find_first_unique_sequence(input, 14)
 }
 
+// Feeds every byte of `reader` to `on_byte`, reading in fixed chunks, until the
+// input runs out or `on_byte` returns false.
+fn for_each_byte<R: Read>(mut reader: R, mut on_byte: impl FnMut(u8) -> bool) -> io::Result<()> {
+    let mut chunk = [0u8; 8192];
+    loop {
+        let read = match reader.read(&mut chunk) {
+            Ok(0) => return Ok(()),
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e)
+        };
+        for &byte in &chunk[..read] {
+            if !on_byte(byte) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// Keeps a count per byte value so each step is O(1). `on_marker` gets every position
+// that ends a window of `sequence_length` distinct bytes and returns whether to keep
+// scanning.
+fn scan_markers<R: Read>(
+    reader: R,
+    sequence_length: usize,
+    mut on_marker: impl FnMut(usize) -> bool,
+) -> io::Result<()> {
+    assert!(sequence_length > 0, "Markers must be at least one symbol long");
+    let mut counts = [0usize; 256];
+    let mut window = vec![0u8; sequence_length];
+    let mut distinct = 0;
+    let mut position = 0;
+    for_each_byte(reader, |byte| {
+        let slot = position % sequence_length;
+        if position >= sequence_length {
+            let removed = window[slot] as usize;
+            counts[removed] -= 1;
+            if counts[removed] == 0 {
+                distinct -= 1;
+            }
+        }
+        window[slot] = byte;
+        counts[byte as usize] += 1;
+        if counts[byte as usize] == 1 {
+            distinct += 1;
+        }
+        position += 1;
+        distinct != sequence_length || on_marker(position)
+    })
+}
+
+pub fn find_first_marker<R: Read>(reader: R, sequence_length: usize) -> io::Result<Option<usize>> {
+    let mut first = None;
+    scan_markers(reader, sequence_length, |position| {
+        first = Some(position);
+        false
+    })?;
+    Ok(first)
+}
+
+pub fn find_all_markers<R: Read>(reader: R, sequence_length: usize) -> io::Result<Vec<usize>> {
+    let mut markers = Vec::new();
+    scan_markers(reader, sequence_length, |position| {
+        markers.push(position);
+        true
+    })?;
+    Ok(markers)
+}
+
+#[aoc(day6, part1, bytes)]
+pub fn solve_part1_bytes(input: &[u8]) -> usize {
+    find_first_marker(input, 4).unwrap()
+        .expect("No valid start sequence in the input")
+}
+
+#[aoc(day6, part2, bytes)]
+pub fn solve_part2_bytes(input: &[u8]) -> usize {
+    find_first_marker(input, 14).unwrap()
+        .expect("No valid start sequence in the input")
+}
+
+// Entry `k - 1` is the first position that ends a window of `k` distinct bytes, for
+// every `k` up to `max_length`. Tracks the longest distinct run ending at each byte,
+// which only ever unlocks the next unanswered lengths, so it is a single pass.
+pub fn marker_report<R: Read>(reader: R, max_length: usize) -> io::Result<Vec<Option<usize>>> {
+    let mut report = vec![None; max_length];
+    let mut last_seen = [0usize; 256];
+    let mut run_start = 0;
+    let mut position = 0;
+    let mut next_length = 1;
+    for_each_byte(reader, |byte| {
+        position += 1;
+        run_start = run_start.max(last_seen[byte as usize]);
+        last_seen[byte as usize] = position;
+        while next_length <= max_length && next_length <= position - run_start {
+            report[next_length - 1] = Some(position);
+            next_length += 1;
+        }
+        next_length <= max_length
+    })?;
+    Ok(report)
+}
+
+// Position that ends the first window of `sequence_length` tokens holding at most
+// `max_repeats` repeats, i.e. at least `sequence_length - max_repeats` distinct tokens.
+pub fn find_first_tolerant_sequence<T, I>(tokens: I, sequence_length: usize, max_repeats: usize) -> Option<usize>
+where
+    T: Eq + Hash + Clone,
+    I: IntoIterator<Item = T>,
+{
+    assert!(sequence_length > 0, "Markers must be at least one symbol long");
+    let mut window: VecDeque<T> = VecDeque::with_capacity(sequence_length);
+    let mut seen: HashMap<T, usize> = HashMap::new();
+    for (i, token) in tokens.into_iter().enumerate() {
+        if window.len() == sequence_length {
+            let removed = window.pop_front().unwrap();
+            let count = seen.get_mut(&removed).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                seen.remove(&removed);
+            }
+        }
+        *seen.entry(token.clone()).or_insert(0) += 1;
+        window.push_back(token);
+        if window.len() == sequence_length && sequence_length - seen.len() <= max_repeats {
+            return Some(i + 1);
+        }
+    }
+    None
+}
+
+pub fn find_first_noisy_marker(input: &str, sequence_length: usize, max_repeats: usize) -> Option<usize> {
+    find_first_tolerant_sequence(input.chars(), sequence_length, max_repeats)
+}
+
+// A `length` byte stream drawn from three letters, so no marker of four or more
+// appears until the run of fourteen distinct letters at its very end.
+pub fn generate_late_marker_stream(length: usize, seed: u64) -> String {
+    let mut rng = Lcg::new(seed);
+    let noise = (0..length.saturating_sub(14)).map(|_| (b'a' + rng.below(3) as u8) as char);
+    noise.chain("abcdefghijklmn".chars()).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::solutions::rng::assert_solvers_agree;
 
     const EXAMPLES: &[(&str, usize, usize)] = &[
         ("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 7, 19),
@@ -58,4 +205,67 @@ mod tests {
             assert_eq!(solve_part2(example), expected);
         }
     }
+
+    #[test]
+    fn test_solve_bytes() {
+        for &(example, part1, part2) in EXAMPLES {
+            assert_eq!(solve_part1_bytes(example.as_bytes()), part1);
+            assert_eq!(solve_part2_bytes(example.as_bytes()), part2);
+        }
+    }
+
+    #[test]
+    fn test_find_all_markers() {
+        assert_eq!(find_all_markers("abcabcdd".as_bytes(), 3).unwrap(), vec![3, 4, 5, 6, 7]);
+        assert_eq!(find_all_markers("aaaa".as_bytes(), 2).unwrap(), Vec::<usize>::new());
+        assert_eq!(find_first_marker("aaaa".as_bytes(), 2).unwrap(), None);
+    }
+
+    #[test]
+    fn test_marker_report() {
+        for &(example, part1, part2) in EXAMPLES {
+            let report = marker_report(example.as_bytes(), 14).unwrap();
+            assert_eq!(report[3], Some(part1));
+            assert_eq!(report[13], Some(part2));
+            for (k, &position) in report.iter().enumerate() {
+                assert_eq!(position, find_first_marker(example.as_bytes(), k + 1).unwrap());
+            }
+        }
+        assert_eq!(marker_report("abcab".as_bytes(), 5).unwrap(), vec![Some(1), Some(2), Some(3), None, None]);
+        assert_eq!(marker_report("".as_bytes(), 2).unwrap(), vec![None, None]);
+    }
+
+    #[test]
+    fn test_find_first_noisy_marker() {
+        for &(example, part1, part2) in EXAMPLES {
+            assert_eq!(find_first_noisy_marker(example, 4, 0), Some(part1));
+            assert_eq!(find_first_noisy_marker(example, 14, 0), Some(part2));
+        }
+        assert_eq!(find_first_noisy_marker("aabbcd", 4, 0), None);
+        assert_eq!(find_first_noisy_marker("aabbcd", 4, 1), Some(5));
+        assert_eq!(find_first_noisy_marker("aabbcd", 4, 2), Some(4));
+        assert_eq!(find_first_noisy_marker("ab", 4, 4), None);
+    }
+
+    #[test]
+    fn test_find_first_tolerant_sequence() {
+        let words = "the cat saw the dog and the bird".split(" ");
+        assert_eq!(find_first_tolerant_sequence(words.clone(), 4, 0), Some(5));
+        assert_eq!(find_first_tolerant_sequence(words, 5, 0), Some(6));
+        assert_eq!(find_first_tolerant_sequence(vec![1, 1, 2, 2, 3, 1], 3, 0), Some(6));
+        assert_eq!(find_first_tolerant_sequence(vec![1, 1, 2, 2, 3, 1], 3, 1), Some(3));
+    }
+
+    #[test]
+    fn test_find_first_marker_across_chunks() {
+        let stream = generate_late_marker_stream(50_000, 6);
+        assert!(find_first_marker(stream.as_bytes(), 4).unwrap().unwrap() > 50_000 - 14);
+        assert_eq!(find_first_marker(stream.as_bytes(), 14).unwrap(), Some(50_000));
+        assert_solvers_agree(
+            6..9,
+            |seed| generate_late_marker_stream(50_000, seed),
+            |stream| (solve_part1(stream), solve_part2(stream)),
+            |stream| (solve_part1_bytes(stream.as_bytes()), solve_part2_bytes(stream.as_bytes())),
+        );
+    }
 }
\ No newline at end of file
@@ -1,100 +1,930 @@
-use std::collections::{BinaryHeap, VecDeque};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use aoc_runner_derive::aoc;
+use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
 
-#[aoc(day7, part1)]
-pub fn solve_part1(raw_input: &str) -> usize {
-    let commands: Vec<&str> = raw_input.split("$ ").collect_vec()[1..].to_vec();
-    let mut stack: VecDeque<(String, usize)> = VecDeque::new();
-    let mut tot: usize = 0;
-    for c in commands {
-        if c.starts_with("cd") {
-            let dir = c.replace("cd ", "").replace("\n", "");
-            if dir == ".." {
-                let (_, size) = stack.pop_back().unwrap();
-                if size <= 100000 {
-                    tot += size;
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+enum TranscriptLine<'a> {
+    Cd(&'a str),
+    Ls,
+    // The operands of `mkdir` and `rm`, still separated by whitespace.
+    Mkdir(&'a str),
+    Rm(&'a str),
+    Mv(&'a str, &'a str),
+    Unknown(&'a str),
+    Output(&'a str),
+}
+
+// Drops leading option words such as `-p` or `-rf`.
+fn strip_flags(args: &str) -> &str {
+    let mut args = args.trim_start();
+    while args.starts_with('-') {
+        args = args.split_once(' ').map_or("", |(_, rest)| rest.trim_start());
+    }
+    args
+}
+
+fn parse_line(line: &str) -> TranscriptLine<'_> {
+    let command = match line.strip_prefix("$ ") {
+        Some(command) => command,
+        None => return TranscriptLine::Output(line)
+    };
+    let (program, args) = command.split_once(' ').unwrap_or((command, ""));
+    match (program, args) {
+        ("ls", "") => TranscriptLine::Ls,
+        ("cd", target) if !target.is_empty() => TranscriptLine::Cd(target),
+        ("mkdir", args) if !strip_flags(args).is_empty() => TranscriptLine::Mkdir(strip_flags(args)),
+        ("rm", args) if !strip_flags(args).is_empty() => TranscriptLine::Rm(strip_flags(args)),
+        ("mv", args) => match strip_flags(args).split_whitespace().collect_tuple() {
+            Some((from, to)) => TranscriptLine::Mv(from, to),
+            None => TranscriptLine::Unknown(command)
+        },
+        _ => TranscriptLine::Unknown(command)
+    }
+}
+
+// Splits a line of `ls` output into the entry name and its size, `None` for a dir.
+// Lines that are neither shape give `None` overall.
+fn parse_listing(line: &str) -> Option<(&str, Option<usize>)> {
+    match line.split_once(' ')? {
+        ("dir", name) => Some((name, None)),
+        (size, name) => size.parse().ok().map(|size| (name, Some(size)))
+    }
+}
+
+// Resolves an absolute or relative `target` against `cwd`. The flag reports whether
+// a `..` tried to climb above the root, which stays at the root instead.
+fn resolve_path(cwd: &[String], target: &str) -> (Vec<String>, bool) {
+    let mut path = if target.starts_with('/') { Vec::new() } else { cwd.to_vec() };
+    let mut above_root = false;
+    for component in target.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => above_root |= path.pop().is_none(),
+            name => path.push(name.to_string())
+        }
+    }
+    (path, above_root)
+}
+
+fn path_string(path: &[String]) -> String {
+    format!("/{}", path.join("/"))
+}
+
+// Whether `path` is `ancestor` or lies somewhere below it.
+fn is_within(path: &str, ancestor: &str) -> bool {
+    ancestor == "/" || path == ancestor || path.starts_with(&format!("{}/", ancestor))
+}
+
+// Applies a command to each of its whitespace-separated operands in turn, carrying on
+// past failures and reporting them all together.
+fn for_each_operand(operands: &str, mut apply: impl FnMut(&str) -> Result<(), String>) -> Result<(), String> {
+    let errors = operands.split_whitespace().filter_map(|operand| apply(operand).err()).collect_vec();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; "))
+    }
+}
+
+// Non-empty transcript lines with their 1-based line numbers.
+fn numbered_lines(transcript: &str) -> impl Iterator<Item = (usize, &str)> {
+    transcript.lines()
+        .map(str::trim_end)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| (i + 1, line))
+}
+
+#[derive(Eq, PartialEq, Debug, Clone, Serialize)]
+pub struct File {
+    pub path: String,
+    pub size: usize,
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Dir {
+    pub path: String,
+    pub children: BTreeMap<String, Node>,
+}
+
+#[derive(Eq, PartialEq, Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Node {
+    Dir(Dir),
+    File(File),
+}
+
+// Whether `name` can stand for a single entry of its directory. Anything else would
+// point somewhere else entirely once joined onto a path.
+fn is_plain_name(name: &str) -> bool {
+    !matches!(name, "" | "." | "..") && !name.contains('/')
+}
+
+fn join_path(parent: &str, name: &str) -> String {
+    if parent == "/" {
+        format!("/{}", name)
+    } else {
+        format!("{}/{}", parent, name)
+    }
+}
+
+impl Node {
+    pub fn path(&self) -> &str {
+        match self {
+            Node::Dir(dir) => &dir.path,
+            Node::File(file) => &file.path
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            Node::Dir(dir) => dir.size(),
+            Node::File(file) => file.size
+        }
+    }
+
+    // Moves the node to `path`, fixing up the paths of everything below it.
+    fn rebase(&mut self, path: String) {
+        match self {
+            Node::File(file) => file.path = path,
+            Node::Dir(dir) => {
+                for (name, child) in dir.children.iter_mut() {
+                    child.rebase(join_path(&path, name));
+                }
+                dir.path = path;
+            }
+        }
+    }
+}
+
+impl Dir {
+    pub fn new(path: String) -> Dir {
+        Dir { path, children: BTreeMap::new() }
+    }
+
+    pub fn name(&self) -> &str {
+        match self.path.rsplit('/').next() {
+            Some("") | None => "/",
+            Some(name) => name
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.children.values().map(Node::size).sum()
+    }
+
+    pub fn subdirs(&self) -> impl Iterator<Item = &Dir> {
+        self.children.values().filter_map(|child| match child {
+            Node::Dir(dir) => Some(dir),
+            Node::File(_) => None
+        })
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &File> {
+        self.children.values().filter_map(|child| match child {
+            Node::File(file) => Some(file),
+            Node::Dir(_) => None
+        })
+    }
+
+    // Returns the named subdirectory, creating it if the transcript never listed it.
+    fn subdir_mut(&mut self, name: &str) -> Result<&mut Dir, String> {
+        let path = join_path(&self.path, name);
+        let child = self.children.entry(name.to_string())
+            .or_insert_with(|| Node::Dir(Dir::new(path)));
+        match child {
+            Node::Dir(dir) => Ok(dir),
+            Node::File(file) => Err(format!("{} is a file, not a directory", file.path))
+        }
+    }
+
+    // Records an entry of `ls` output. Listing an entry again replaces it rather than
+    // counting it twice, and relisting a directory keeps what is already known inside.
+    fn add_listing(&mut self, (name, size): (&str, Option<usize>)) -> Result<(), String> {
+        if !is_plain_name(name) {
+            return Err(format!("Invalid entry name in {}: {:?}", self.path, name));
+        }
+        match size {
+            None => self.subdir_mut(name).map(|_| ()),
+            Some(size) => {
+                let path = join_path(&self.path, name);
+                if let Some(Node::Dir(dir)) = self.children.get(name) {
+                    return Err(format!("{} is a directory, not a file", dir.path));
+                }
+                self.children.insert(name.to_string(), Node::File(File { path, size }));
+                Ok(())
+            }
+        }
+    }
+
+    fn collect_sizes<'a>(&'a self, sizes: &mut Vec<(&'a str, usize)>) -> usize {
+        let size = self.files().map(|file| file.size).sum::<usize>()
+            + self.subdirs().map(|dir| dir.collect_sizes(sizes)).sum::<usize>();
+        sizes.push((&self.path, size));
+        size
+    }
+}
+
+// Directories serialize with their total size so the JSON export carries what the
+// puzzle computes, not just the raw listing.
+impl Serialize for Dir {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Dir", 3)?;
+        state.serialize_field("path", &self.path)?;
+        state.serialize_field("size", &self.size())?;
+        state.serialize_field("children", &self.children)?;
+        state.end()
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct ReplayWarning {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ReplayWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Filesystem {
+    pub root: Dir,
+}
+
+impl Filesystem {
+    pub fn new() -> Filesystem {
+        Filesystem { root: Dir::new(String::from("/")) }
+    }
+
+    pub fn from_transcript(transcript: &str) -> Filesystem {
+        Filesystem::replay(transcript).0
+    }
+
+    // Rebuilds the tree from a shell session. Besides `cd` and `ls` this understands
+    // absolute and multi-level paths, `mkdir`, `rm` and `mv`. Anything it can't make
+    // sense of is skipped and reported as a warning.
+    pub fn replay(transcript: &str) -> (Filesystem, Vec<ReplayWarning>) {
+        let mut filesystem = Filesystem::new();
+        let mut warnings = Vec::new();
+        let mut cwd: Vec<String> = Vec::new();
+        let mut last_command = TranscriptLine::Ls;
+        for (number, line) in numbered_lines(transcript) {
+            let parsed = parse_line(line);
+            let outcome = match parsed {
+                TranscriptLine::Cd(target) => {
+                    let (path, above_root) = resolve_path(&cwd, target);
+                    match filesystem.dir_mut(&path) {
+                        Err(message) => Err(message),
+                        Ok(_) if above_root => {
+                            cwd = path;
+                            Err(format!("cd {} goes above /", target))
+                        }
+                        Ok(_) => {
+                            cwd = path;
+                            Ok(())
+                        }
+                    }
+                }
+                TranscriptLine::Ls => Ok(()),
+                TranscriptLine::Mkdir(targets) => for_each_operand(targets, |target| {
+                    filesystem.dir_mut(&resolve_path(&cwd, target).0).map(|_| ())
+                }),
+                TranscriptLine::Rm(targets) => for_each_operand(targets, |target| {
+                    filesystem.remove(&resolve_path(&cwd, target).0).map(|_| ())
+                }),
+                TranscriptLine::Mv(from, to) => {
+                    filesystem.rename(&resolve_path(&cwd, from).0, &resolve_path(&cwd, to).0)
+                }
+                TranscriptLine::Unknown(command) => Err(format!("Unknown command: {}", command)),
+                TranscriptLine::Output(output) => match last_command {
+                    TranscriptLine::Ls => match parse_listing(output) {
+                        Some(entry) => filesystem.dir_mut(&cwd).and_then(|dir| dir.add_listing(entry)),
+                        None => Err(format!("Unrecognised ls output: {}", output))
+                    },
+                    TranscriptLine::Unknown(_) => Ok(()),
+                    _ => Err(format!("Unexpected output: {}", output))
                 }
-                let (parent, parent_size) = stack.pop_back()
-                    .unwrap();
-                stack.push_back((parent, parent_size + size));
+            };
+            if let Err(message) = outcome {
+                warnings.push(ReplayWarning { line: number, message });
+            }
+            if !matches!(parsed, TranscriptLine::Output(_)) {
+                last_command = parsed;
+            }
+        }
+        (filesystem, warnings)
+    }
+
+    // Walks to the directory at `path`, creating any missing directories on the way.
+    fn dir_mut(&mut self, path: &[String]) -> Result<&mut Dir, String> {
+        path.iter().try_fold(&mut self.root, |dir, name| dir.subdir_mut(name))
+    }
+
+    fn existing_dir_mut(&mut self, path: &[String]) -> Option<&mut Dir> {
+        path.iter().try_fold(&mut self.root, |dir, name| match dir.children.get_mut(name) {
+            Some(Node::Dir(subdir)) => Some(subdir),
+            _ => None
+        })
+    }
+
+    fn remove(&mut self, path: &[String]) -> Result<Node, String> {
+        let (name, parent) = path.split_last()
+            .ok_or_else(|| String::from("Refusing to remove /"))?;
+        self.existing_dir_mut(parent)
+            .and_then(|dir| dir.children.remove(name))
+            .ok_or_else(|| format!("No such file or directory: {}", path_string(path)))
+    }
+
+    // `Some(true)` for a directory, `Some(false)` for a file, `None` if nothing is there.
+    fn kind_at(&mut self, path: &[String]) -> Option<bool> {
+        match path.split_last() {
+            None => Some(true),
+            Some((name, parent)) => self.existing_dir_mut(parent)?
+                .children
+                .get(name)
+                .map(|node| matches!(node, Node::Dir(_)))
+        }
+    }
+
+    // Follows `mv`: into `to` when it is a directory, otherwise renaming to `to`.
+    fn rename(&mut self, from: &[String], to: &[String]) -> Result<(), String> {
+        let moving_dir = self.kind_at(from)
+            .ok_or_else(|| format!("No such file or directory: {}", path_string(from)))?;
+        let mut destination = to.to_vec();
+        if self.kind_at(to) == Some(true) {
+            destination.push(from.last().cloned().unwrap_or_default());
+        }
+        if destination.starts_with(from) {
+            return Err(format!("Cannot move {} into itself", path_string(from)));
+        }
+        match self.kind_at(&destination) {
+            Some(true) => return Err(format!("Cannot overwrite directory {}", path_string(&destination))),
+            Some(false) if moving_dir => {
+                return Err(format!("Cannot overwrite file {} with a directory", path_string(&destination)));
+            }
+            _ => {}
+        }
+        let (name, parent) = destination.split_last().unwrap();
+        if self.existing_dir_mut(parent).is_none() {
+            return Err(format!("No such directory: {}", path_string(parent)));
+        }
+        let mut node = self.remove(from)?;
+        node.rebase(path_string(&destination));
+        self.existing_dir_mut(parent).unwrap().children.insert(name.clone(), node);
+        Ok(())
+    }
+
+    pub fn dirs(&self) -> Vec<&Dir> {
+        let mut dirs = vec![&self.root];
+        let mut i = 0;
+        while i < dirs.len() {
+            dirs.extend(dirs[i].subdirs());
+            i += 1;
+        }
+        dirs
+    }
+
+    // Size of every directory keyed by its full path, children before their parents,
+    // computed in one walk of the tree.
+    pub fn dir_sizes(&self) -> Vec<(&str, usize)> {
+        let mut sizes = Vec::new();
+        self.root.collect_sizes(&mut sizes);
+        sizes
+    }
+
+    pub fn total_size(&self) -> usize {
+        self.root.size()
+    }
+}
+
+impl Default for Filesystem {
+    fn default() -> Self {
+        Filesystem::new()
+    }
+}
+
+// The puzzle's `- / (dir)` listing, children in name order.
+pub fn render_tree(filesystem: &Filesystem) -> String {
+    fn render_dir(dir: &Dir, depth: usize, lines: &mut Vec<String>) {
+        lines.push(format!("{}- {} (dir)", "  ".repeat(depth), dir.name()));
+        for (name, child) in &dir.children {
+            match child {
+                Node::Dir(subdir) => render_dir(subdir, depth + 1, lines),
+                Node::File(file) => {
+                    lines.push(format!("{}- {} (file, size={})", "  ".repeat(depth + 1), name, file.size));
+                }
+            }
+        }
+    }
+    let mut lines = Vec::new();
+    render_dir(&filesystem.root, 0, &mut lines);
+    lines.join("\n")
+}
+
+// Sizes the way `du -h` prints them: powers of 1024, rounded up, with one decimal
+// below ten.
+pub fn human_size(bytes: usize) -> String {
+    let units = ["K", "M", "G", "T", "P"];
+    if bytes < 1024 {
+        return bytes.to_string();
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    loop {
+        // Round before settling on the unit, so 1023.9K becomes 1.0M rather than 1024K.
+        let rounded = if (value * 10.0).ceil() < 100.0 { (value * 10.0).ceil() / 10.0 } else { value.ceil() };
+        if rounded < 1024.0 || unit + 1 == units.len() {
+            return if rounded < 10.0 {
+                format!("{:.1}{}", rounded, units[unit])
             } else {
-                stack.push_back((dir, 0));
-            }
-        } else {
-            let (dir, mut size) = stack.pop_back().unwrap();
-            c.split("\n").collect_vec()[1..].into_iter().for_each(|s| {
-                let maybe_file_size = s.split(" ")
-                    .collect_vec()
-                    .first()
-                    .unwrap()
-                    .parse::<usize>();
-                if maybe_file_size.is_ok() {
-                    size += maybe_file_size.unwrap();
+                format!("{}{}", rounded, units[unit])
+            };
+        }
+        value /= 1024.0;
+        unit += 1;
+    }
+}
+
+// Every directory with its total size, largest first, like `du -h | sort -rh`.
+pub fn render_du(filesystem: &Filesystem) -> String {
+    filesystem.dir_sizes()
+        .into_iter()
+        .sorted_by(|(a_path, a_size), (b_path, b_size)| b_size.cmp(a_size).then(a_path.cmp(b_path)))
+        .into_iter()
+        .map(|(path, size)| format!("{}\t{}", human_size(size), path))
+        .join("\n")
+}
+
+pub fn to_json(filesystem: &Filesystem) -> String {
+    serde_json::to_string_pretty(&filesystem.root).unwrap()
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum TranscriptIssue {
+    UnlistedDirectory { line: usize, path: String },
+    AboveRoot { line: usize },
+    ConflictingListing { line: usize, path: String, first_listed: usize },
+    UnvisitedDirectory { line: usize, path: String },
+}
+
+impl TranscriptIssue {
+    pub fn line(&self) -> usize {
+        match self {
+            TranscriptIssue::UnlistedDirectory { line, .. } => *line,
+            TranscriptIssue::AboveRoot { line } => *line,
+            TranscriptIssue::ConflictingListing { line, .. } => *line,
+            TranscriptIssue::UnvisitedDirectory { line, .. } => *line
+        }
+    }
+}
+
+impl fmt::Display for TranscriptIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TranscriptIssue::UnlistedDirectory { line, path } => {
+                write!(f, "line {}: cd into {}, which was never listed", line, path)
+            }
+            TranscriptIssue::AboveRoot { line } => write!(f, "line {}: cd .. above /", line),
+            TranscriptIssue::ConflictingListing { line, path, first_listed } => {
+                write!(f, "line {}: ls of {} conflicts with the listing at line {}", line, path, first_listed)
+            }
+            TranscriptIssue::UnvisitedDirectory { line, path } => {
+                write!(f, "line {}: {} is listed but never visited", line, path)
+            }
+        }
+    }
+}
+
+struct Listing {
+    line: usize,
+    entries: BTreeMap<String, Option<usize>>,
+}
+
+fn finish_listing(
+    current: &mut Option<(String, Listing)>,
+    listings: &mut HashMap<String, Listing>,
+    issues: &mut Vec<TranscriptIssue>,
+) {
+    if let Some((path, listing)) = current.take() {
+        match listings.get(&path) {
+            Some(earlier) if earlier.entries != listing.entries => {
+                issues.push(TranscriptIssue::ConflictingListing {
+                    line: listing.line,
+                    path,
+                    first_listed: earlier.line,
+                });
+            }
+            Some(_) => {}
+            None => {
+                listings.insert(path, listing);
+            }
+        }
+    }
+}
+
+// Keeps the stored listing of the directory holding `path` in step with a command that
+// creates (`Some(size)`, `None` for a directory) or removes (`None`) the entry there.
+fn update_listing(listings: &mut HashMap<String, Listing>, path: &[String], entry: Option<Option<usize>>) {
+    if let Some((name, parent)) = path.split_last() {
+        if let Some(listing) = listings.get_mut(&path_string(parent)) {
+            match entry {
+                Some(size) => listing.entries.insert(name.clone(), size),
+                None => listing.entries.remove(name),
+            };
+        }
+    }
+}
+
+// Forgets every key at or below `from`, re-adding them under `to` when given.
+fn move_paths<V>(map: &mut HashMap<String, V>, from: &str, to: Option<&str>) {
+    let moved = map.keys().filter(|path| is_within(path, from)).cloned().collect_vec();
+    for path in moved {
+        let value = map.remove(&path).unwrap();
+        if let Some(to) = to {
+            map.insert(format!("{}{}", to, &path[from.len()..]), value);
+        }
+    }
+}
+
+// Checks the transcript against itself without building the tree, so problems in
+// the input show up with the line that caused them. Issues come back in line order.
+pub fn validate_transcript(transcript: &str) -> Vec<TranscriptIssue> {
+    let mut issues = Vec::new();
+    let mut cwd: Vec<String> = Vec::new();
+    let mut listed_dirs: HashMap<String, usize> = HashMap::new();
+    let mut visited: HashMap<String, usize> = HashMap::from([(String::from("/"), 0)]);
+    let mut listings: HashMap<String, Listing> = HashMap::new();
+    let mut current: Option<(String, Listing)> = None;
+    for (number, line) in numbered_lines(transcript) {
+        let parsed = parse_line(line);
+        if !matches!(parsed, TranscriptLine::Output(_)) {
+            finish_listing(&mut current, &mut listings, &mut issues);
+        }
+        match parsed {
+            TranscriptLine::Cd(target) => {
+                let (path, above_root) = resolve_path(&cwd, target);
+                let full_path = path_string(&path);
+                if above_root {
+                    issues.push(TranscriptIssue::AboveRoot { line: number });
+                }
+                if !path.is_empty() && !listed_dirs.contains_key(&full_path) {
+                    issues.push(TranscriptIssue::UnlistedDirectory { line: number, path: full_path.clone() });
+                }
+                visited.entry(full_path).or_insert(number);
+                cwd = path;
+            }
+            TranscriptLine::Ls => {
+                current = Some((path_string(&cwd), Listing { line: number, entries: BTreeMap::new() }));
+            }
+            TranscriptLine::Mkdir(targets) => {
+                for target in targets.split_whitespace() {
+                    // Like `replay`, every missing parent comes into being too.
+                    let path = resolve_path(&cwd, target).0;
+                    for end in 1..=path.len() {
+                        update_listing(&mut listings, &path[..end], Some(None));
+                        let prefix = path_string(&path[..end]);
+                        listed_dirs.entry(prefix.clone()).or_insert(number);
+                        visited.entry(prefix).or_insert(number);
+                    }
+                }
+            }
+            TranscriptLine::Rm(targets) => {
+                for target in targets.split_whitespace() {
+                    let path = resolve_path(&cwd, target).0;
+                    update_listing(&mut listings, &path, None);
+                    let path = path_string(&path);
+                    move_paths(&mut listed_dirs, &path, None);
+                    move_paths(&mut visited, &path, None);
+                    move_paths(&mut listings, &path, None);
+                }
+            }
+            TranscriptLine::Mv(from, to) => {
+                let (from_path, mut to_path) = (resolve_path(&cwd, from).0, resolve_path(&cwd, to).0);
+                let (from, mut to) = (path_string(&from_path), path_string(&to_path));
+                let into_dir = listed_dirs.contains_key(&to) || visited.contains_key(&to);
+                if let (true, Some(name)) = (into_dir, from_path.last()) {
+                    to = join_path(&to, name);
+                    to_path.push(name.clone());
+                }
+                // The entry leaves its old listing; if its size was never listed there,
+                // the new parent's listing can no longer be trusted.
+                let entry = from_path.split_last()
+                    .and_then(|(name, parent)| listings.get(&path_string(parent))?.entries.get(name).copied());
+                update_listing(&mut listings, &from_path, None);
+                match (entry, to_path.split_last()) {
+                    (Some(_), _) => update_listing(&mut listings, &to_path, entry),
+                    (None, Some((_, parent))) => {
+                        listings.remove(&path_string(parent));
+                    }
+                    (None, None) => {}
+                }
+                let moved_dir = listed_dirs.contains_key(&from);
+                move_paths(&mut listed_dirs, &from, Some(&to));
+                move_paths(&mut visited, &from, Some(&to));
+                move_paths(&mut listings, &from, Some(&to));
+                if moved_dir {
+                    visited.entry(to).or_insert(number);
                 }
-            });
-            stack.push_back((dir, size));
+            }
+            TranscriptLine::Unknown(_) => {}
+            TranscriptLine::Output(output) => {
+                if let (Some((path, listing)), Some((name, size))) = (current.as_mut(), parse_listing(output)) {
+                    if size.is_none() {
+                        listed_dirs.entry(join_path(path, name)).or_insert(number);
+                    }
+                    listing.entries.insert(name.to_string(), size);
+                }
+            }
         }
     }
-    while stack.len() > 1 {
-        let (_, size) = stack.pop_back().unwrap();
-        if size <= 100000 {
-            tot += size;
+    finish_listing(&mut current, &mut listings, &mut issues);
+    issues.extend(listed_dirs.into_iter()
+        .filter(|(path, _)| !visited.contains_key(path))
+        .map(|(path, line)| TranscriptIssue::UnvisitedDirectory { line, path }));
+    issues.sort_by_key(|issue| issue.line());
+    issues
+}
+
+// Recreates the tree under `target`, writing each file as a sparse file of its size.
+// Entry names that would reach outside their directory are refused before anything
+// is written for them.
+pub fn materialize(filesystem: &Filesystem, target: &Path) -> io::Result<()> {
+    fn write_dir(dir: &Dir, target: &Path) -> io::Result<()> {
+        if let Some(name) = dir.children.keys().find(|name| !is_plain_name(name)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid entry name in {}: {:?}", dir.path, name),
+            ));
         }
-        let (parent, parent_size) = stack.pop_back()
-            .unwrap();
-        stack.push_back((parent, parent_size + size));
+        fs::create_dir_all(target)?;
+        for (name, child) in &dir.children {
+            match child {
+                Node::Dir(subdir) => write_dir(subdir, &target.join(name))?,
+                Node::File(file) => fs::File::create(target.join(name))?.set_len(file.size as u64)?
+            }
+        }
+        Ok(())
     }
-    tot
+    write_dir(&filesystem.root, target)
+}
+
+// Materializes into a fresh directory under the system temp dir and returns its path.
+// Removing it afterwards is up to the caller.
+pub fn materialize_temp(filesystem: &Filesystem) -> io::Result<PathBuf> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let target = std::env::temp_dir().join(format!(
+        "aoc2022-day7-{}-{}",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+    materialize(filesystem, &target)?;
+    Ok(target)
+}
+
+// Walks a real directory and writes the `$ cd` / `$ ls` session that explores it,
+// entries in name order. Anything that isn't a plain file or directory is skipped.
+pub fn transcript_from_dir(root: &Path) -> io::Result<String> {
+    fn explore(dir: &Path, lines: &mut Vec<String>) -> io::Result<()> {
+        let mut entries = fs::read_dir(dir)?
+            .map(|entry| {
+                let entry = entry?;
+                Ok((entry.file_name().to_string_lossy().into_owned(), fs::symlink_metadata(entry.path())?))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        lines.push(String::from("$ ls"));
+        for (name, metadata) in &entries {
+            if metadata.is_dir() {
+                lines.push(format!("dir {}", name));
+            } else if metadata.is_file() {
+                lines.push(format!("{} {}", metadata.len(), name));
+            }
+        }
+        for (name, metadata) in &entries {
+            if metadata.is_dir() {
+                lines.push(format!("$ cd {}", name));
+                explore(&dir.join(name), lines)?;
+                lines.push(String::from("$ cd .."));
+            }
+        }
+        Ok(())
+    }
+    let mut lines = vec![String::from("$ cd /")];
+    explore(root, &mut lines)?;
+    Ok(lines.join("\n"))
+}
+
+#[aoc_generator(day7)]
+pub fn generator(raw_input: &str) -> Filesystem {
+    Filesystem::from_transcript(raw_input)
+}
+
+#[aoc(day7, part1)]
+pub fn solve_part1(filesystem: &Filesystem) -> usize {
+    filesystem.dir_sizes()
+        .into_iter()
+        .map(|(_, size)| size)
+        .filter(|&size| size <= 100000)
+        .sum()
+}
+
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub struct DiskGeometry {
+    pub capacity: usize,
+    pub required_free: usize,
+}
+
+pub const PUZZLE_DISK: DiskGeometry = DiskGeometry {
+    capacity: 70000000,
+    required_free: 30000000,
+};
+
+impl DiskGeometry {
+    pub fn size_to_free(&self, used: usize) -> usize {
+        (used + self.required_free).saturating_sub(self.capacity)
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum Deletion {
+    NothingToDelete,
+    Delete { path: String, size: usize },
+    // Even an empty disk wouldn't have the required space.
+    Impossible { size_to_free: usize },
+}
+
+// Smallest directory whose deletion leaves `disk.required_free` bytes available.
+pub fn choose_deletion(filesystem: &Filesystem, disk: DiskGeometry) -> Deletion {
+    let size_to_free = disk.size_to_free(filesystem.total_size());
+    if size_to_free == 0 {
+        return Deletion::NothingToDelete;
+    }
+    filesystem.dir_sizes()
+        .into_iter()
+        .filter(|&(_, size)| size >= size_to_free)
+        .min_by_key(|&(path, size)| (size, path))
+        .map(|(path, size)| Deletion::Delete { path: path.to_string(), size })
+        .unwrap_or(Deletion::Impossible { size_to_free })
 }
 
 #[aoc(day7, part2)]
-pub fn solve_part2(raw_input: &str) -> usize {
-    let commands: Vec<&str> = raw_input.split("$ ").collect_vec()[1..].to_vec();
-    let mut stack: VecDeque<(String, usize)> = VecDeque::new();
-    let mut dir_sizes: BinaryHeap<usize> = BinaryHeap::new();
-    for c in commands {
-        if c.starts_with("cd") {
-            let dir = c.replace("cd ", "").replace("\n", "");
-            if dir == ".." {
-                let (_, size) = stack.pop_back().unwrap();
-                dir_sizes.push(size);
-                let (parent, parent_size) = stack.pop_back()
-                    .unwrap();
-                stack.push_back((parent, parent_size + size));
+pub fn solve_part2(filesystem: &Filesystem) -> usize {
+    match choose_deletion(filesystem, PUZZLE_DISK) {
+        Deletion::Delete { size, .. } => size,
+        Deletion::NothingToDelete => 0,
+        Deletion::Impossible { size_to_free } => panic!("Cannot free {} bytes on this disk", size_to_free)
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct DeletionPlan {
+    pub paths: Vec<String>,
+    pub freed: usize,
+}
+
+// Bitset of the totals below `limit` that can be freed.
+#[derive(Clone)]
+struct Totals {
+    limit: usize,
+    words: Vec<u64>,
+}
+
+impl Totals {
+    fn only_zero(limit: usize) -> Totals {
+        let mut words = vec![0; limit.div_ceil(64)];
+        words[0] = 1;
+        Totals { limit, words }
+    }
+
+    fn contains(&self, total: usize) -> bool {
+        total < self.limit && self.words[total / 64] & (1 << (total % 64)) != 0
+    }
+
+    // The smallest reachable total at or above `from`, if it is below the limit.
+    fn next_from(&self, from: usize) -> Option<usize> {
+        if from >= self.limit {
+            return None;
+        }
+        let first = self.words[from / 64] & (u64::MAX << (from % 64));
+        std::iter::once((from / 64, first))
+            .chain(self.words.iter().copied().enumerate().skip(from / 64 + 1))
+            .find(|&(_, word)| word != 0)
+            .map(|(i, word)| i * 64 + word.trailing_zeros() as usize)
+            .filter(|&total| total < self.limit)
+    }
+
+    // This set joined with `other` shifted up by `offset`.
+    fn union_shifted(&self, other: &Totals, offset: usize) -> Totals {
+        let mut words = self.words.clone();
+        let (word_shift, bit_shift) = (offset / 64, offset % 64);
+        for (i, word) in words.iter_mut().enumerate().skip(word_shift) {
+            let low = other.words[i - word_shift] << bit_shift;
+            let high = if bit_shift > 0 && i > word_shift {
+                other.words[i - word_shift - 1] >> (64 - bit_shift)
             } else {
-                stack.push_back((dir, 0));
-            }
-        } else {
-            let (dir, mut size) = stack.pop_back().unwrap();
-            c.split("\n").collect_vec()[1..].into_iter().for_each(|s| {
-                let maybe_file_size = s.split(" ")
-                    .collect_vec()
-                    .first()
-                    .unwrap()
-                    .parse::<usize>();
-                if maybe_file_size.is_ok() {
-                    size += maybe_file_size.unwrap();
-                }
-            });
-            stack.push_back((dir, size));
+                0
+            };
+            *word |= low | high;
+        }
+        if !self.limit.is_multiple_of(64) {
+            *words.last_mut().unwrap() &= (1 << (self.limit % 64)) - 1;
         }
+        Totals { limit: self.limit, words }
     }
-    while stack.len() > 1 {
-        let (_, size) = stack.pop_back().unwrap();
-        dir_sizes.push(size);
-        let (parent, parent_size) = stack.pop_back()
-            .unwrap();
-        stack.push_back((parent, parent_size + size));
+}
+
+// Most bitset words `plan_deletions` will hold at once, 32 MiB.
+const PLAN_WORD_BUDGET: usize = 1 << 22;
+
+// Knapsack over the tree: the set of non-nested directories that frees at least the
+// required space while deleting as few bytes as possible. `None` when even deleting
+// everything isn't enough; an empty plan when nothing needs deleting.
+//
+// Walking the directories in pre-order, deleting one skips straight past its
+// subtree, so `reachable[i]` (totals below the target using dirs from `i` on) and
+// `enough[i]` (the smallest total at or above it) each take one step to fill in.
+// That is O(dirs * size_to_free / 64) time and memory, so when that would pass
+// `PLAN_WORD_BUDGET` sizes are counted in the smallest power-of-two unit that fits.
+// Rounding sizes down and the target up keeps every plan sufficient, at the cost of
+// possibly freeing up to a unit per directory more than the best plan would.
+pub fn plan_deletions(filesystem: &Filesystem, disk: DiskGeometry) -> Option<DeletionPlan> {
+    let size_to_free = disk.size_to_free(filesystem.total_size());
+    if size_to_free == 0 {
+        return Some(DeletionPlan { paths: vec![], freed: 0 });
     }
-    let cur_tot_size = stack.pop_back().unwrap().1;
-    let size_to_free: usize = 30000000 + cur_tot_size - 70000000;
-    let mut ret = dir_sizes.pop().unwrap();
-    while dir_sizes.len() > 1 && &size_to_free < dir_sizes.peek().unwrap() {
-        ret = dir_sizes.pop().unwrap();
+    let sizes: HashMap<&str, usize> = filesystem.dir_sizes().into_iter().collect();
+    let mut preorder: Vec<(&Dir, usize)> = Vec::new();
+    fn visit<'a>(dir: &'a Dir, preorder: &mut Vec<(&'a Dir, usize)>) {
+        let index = preorder.len();
+        preorder.push((dir, 0));
+        dir.subdirs().for_each(|subdir| visit(subdir, preorder));
+        preorder[index].1 = preorder.len();
+    }
+    visit(&filesystem.root, &mut preorder);
+
+    let count = preorder.len();
+    let mut unit: usize = 1;
+    while (count + 1) * size_to_free.div_ceil(unit).div_ceil(64) > PLAN_WORD_BUDGET {
+        unit *= 2;
     }
-    ret
+    let target = size_to_free.div_ceil(unit);
+    let scaled = |dir: &Dir| sizes[dir.path.as_str()] / unit;
+
+    let mut reachable = vec![Totals::only_zero(target); count + 1];
+    let mut enough: Vec<Option<usize>> = vec![None; count + 1];
+    for i in (0..count).rev() {
+        let (dir, end) = preorder[i];
+        let size = scaled(dir);
+        reachable[i] = reachable[i + 1].union_shifted(&reachable[end], size);
+        enough[i] = [
+            enough[i + 1],
+            reachable[end].next_from(target.saturating_sub(size)).map(|total| total + size),
+            enough[end].map(|total| total + size),
+        ].iter().filter_map(|&total| total).min();
+    }
+
+    let mut remaining = match enough[0] {
+        Some(total) => total,
+        // Rounding down can lose the last few bytes that made deleting everything
+        // just enough; the whole tree is then the only plan there is.
+        None if sizes["/"] >= size_to_free => {
+            return Some(DeletionPlan { paths: vec![String::from("/")], freed: sizes["/"] });
+        }
+        None => return None,
+    };
+    let mut paths = Vec::new();
+    let (mut i, mut above_target) = (0, true);
+    while remaining > 0 {
+        let (dir, end) = preorder[i];
+        let size = scaled(dir);
+        let skip = match above_target {
+            true => enough[i + 1] == Some(remaining),
+            false => reachable[i + 1].contains(remaining)
+        };
+        if skip {
+            i += 1;
+            continue;
+        }
+        paths.push(dir.path.clone());
+        remaining -= size;
+        above_target = remaining >= target;
+        i = end;
+    }
+    paths.sort();
+    let freed = paths.iter().map(|path| sizes[path.as_str()]).sum();
+    Some(DeletionPlan { paths, freed })
 }
 
 #[cfg(test)]
@@ -125,13 +955,326 @@ $ ls
 5626152 d.ext
 7214296 k";
 
+    #[test]
+    fn test_generator() {
+        let filesystem = generator(EXAMPLE);
+        let sizes = filesystem.dir_sizes();
+        assert_eq!(sizes, vec![
+            ("/a/e", 584),
+            ("/a", 94853),
+            ("/d", 24933642),
+            ("/", 48381165),
+        ]);
+        let paths: Vec<&str> = filesystem.dirs().into_iter().map(|dir| dir.path.as_str()).collect();
+        assert_eq!(paths, vec!["/", "/a", "/d", "/a/e"]);
+    }
+
     #[test]
     fn test_solve_part1() {
-        assert_eq!(solve_part1(&EXAMPLE), 95437);
+        assert_eq!(solve_part1(&generator(EXAMPLE)), 95437);
     }
 
     #[test]
     fn test_solve_part2() {
-        assert_eq!(solve_part2(&EXAMPLE), 24933642);
+        assert_eq!(solve_part2(&generator(EXAMPLE)), 24933642);
+    }
+
+    #[test]
+    fn test_choose_deletion() {
+        let filesystem = generator(EXAMPLE);
+        assert_eq!(choose_deletion(&filesystem, PUZZLE_DISK), Deletion::Delete {
+            path: String::from("/d"),
+            size: 24933642,
+        });
+        let roomy = DiskGeometry { capacity: 100000000, required_free: 30000000 };
+        assert_eq!(choose_deletion(&filesystem, roomy), Deletion::NothingToDelete);
+        let tight = DiskGeometry { capacity: 48381165, required_free: 90000 };
+        assert_eq!(choose_deletion(&filesystem, tight), Deletion::Delete {
+            path: String::from("/a"),
+            size: 94853,
+        });
+        let tiny = DiskGeometry { capacity: 10, required_free: 20 };
+        assert_eq!(choose_deletion(&filesystem, tiny), Deletion::Impossible { size_to_free: 48381175 });
+    }
+
+    #[test]
+    fn test_render_tree() {
+        let expected = "- / (dir)
+  - a (dir)
+    - e (dir)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)";
+        assert_eq!(render_tree(&generator(EXAMPLE)), expected);
+    }
+
+    #[test]
+    fn test_render_du() {
+        assert_eq!(render_du(&generator(EXAMPLE)), "47M\t/\n24M\t/d\n93K\t/a\n584\t/a/e");
+        assert_eq!(human_size(1024), "1.0K");
+        assert_eq!(human_size(1025), "1.1K");
+        assert_eq!(human_size(10238), "10K");
+        assert_eq!(human_size(3 * 1024 * 1024 * 1024), "3.0G");
+        assert_eq!(human_size(1024 * 1024 - 1), "1.0M");
+        assert_eq!(human_size(1024 * 1024 + 1), "1.1M");
+    }
+
+    #[test]
+    fn test_to_json() {
+        let json: serde_json::Value = serde_json::from_str(&to_json(&generator(EXAMPLE))).unwrap();
+        assert_eq!(json["size"], 48381165);
+        assert_eq!(json["children"]["a"]["type"], "dir");
+        assert_eq!(json["children"]["a"]["size"], 94853);
+        assert_eq!(json["children"]["a"]["children"]["e"]["children"]["i"], serde_json::json!({
+            "type": "file",
+            "path": "/a/e/i",
+            "size": 584
+        }));
+    }
+
+    #[test]
+    fn test_materialize_round_trip() {
+        let filesystem = generator(EXAMPLE);
+        let target = materialize_temp(&filesystem).unwrap();
+        assert_eq!(fs::metadata(target.join("a/e/i")).unwrap().len(), 584);
+        assert!(target.join("d").is_dir());
+        let transcript = transcript_from_dir(&target).unwrap();
+        fs::remove_dir_all(&target).unwrap();
+        assert!(transcript.starts_with("$ cd /\n$ ls\ndir a\n14848514 b.txt\n8504156 c.dat\ndir d\n$ cd a\n"));
+        assert_eq!(generator(&transcript), filesystem);
+    }
+
+    #[test]
+    fn test_materialize_rejects_escaping_names() {
+        let (filesystem, warnings) = Filesystem::replay("$ cd /\n$ ls\n7 ../outside.txt\n9 /tmp/abs.txt\ndir .\n1 ok");
+        assert_eq!(warnings.iter().map(|w| w.line).collect_vec(), vec![3, 4, 5]);
+        assert!(warnings[0].message.contains("../outside.txt"));
+        assert_eq!(filesystem.root.children.keys().collect_vec(), vec!["ok"]);
+
+        let mut filesystem = Filesystem::new();
+        filesystem.root.children.insert(
+            String::from("../outside.txt"),
+            Node::File(File { path: String::from("/../outside.txt"), size: 7 }),
+        );
+        let target = std::env::temp_dir().join(format!("aoc2022-day7-escape-{}", process::id()));
+        let error = materialize(&filesystem, &target.join("root")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn test_validate_transcript_after_changes() {
+        let cases = [
+            ("rm b", "1 e"),
+            ("mkdir c", "5 b\ndir c\n1 e"),
+            ("mv b c", "5 c\n1 e"),
+            ("mkdir -p c d", "5 b\ndir c\ndir d\n1 e"),
+            ("rm b e", ""),
+        ];
+        for (command, listing) in cases {
+            let transcript = format!("$ cd /\n$ ls\n5 b\n1 e\n$ {}\n$ ls\n{}", command, listing);
+            let issues = validate_transcript(&transcript);
+            assert!(!issues.iter().any(|issue| matches!(issue, TranscriptIssue::ConflictingListing { .. })), "{}", command);
+        }
+        let issues = validate_transcript("$ cd /\n$ ls\n5 b\n$ mv b c\n$ ls\n5 b");
+        assert_eq!(issues, vec![TranscriptIssue::ConflictingListing { line: 5, path: String::from("/"), first_listed: 2 }]);
+        let issues = validate_transcript("$ cd /\n$ ls\n5 b\n$ mkdir -p c d\n$ ls\n5 b\ndir c\ndir d\n$ cd c\n$ cd ../d");
+        assert_eq!(issues, vec![]);
+        let transcript = "$ cd /\n$ mkdir -p x/y\n$ cd x\n$ ls\ndir y\n$ cd y";
+        assert_eq!(validate_transcript(transcript), vec![]);
+        assert_eq!(Filesystem::replay(transcript).1, vec![]);
+    }
+
+    #[test]
+    fn test_multiple_operands() {
+        let (filesystem, warnings) = Filesystem::replay("$ cd /\n$ mkdir -p a b\n$ ls\n1 x\n2 y\n$ rm x y z");
+        assert_eq!(filesystem.root.children.keys().collect_vec(), vec!["a", "b"]);
+        assert_eq!(warnings, vec![ReplayWarning { line: 6, message: String::from("No such file or directory: /z") }]);
+    }
+
+    #[test]
+    fn test_validate_transcript() {
+        assert_eq!(validate_transcript(EXAMPLE), vec![]);
+        let transcript = "$ cd /
+$ ls
+dir a
+dir b
+1 x
+$ cd ..
+$ cd c
+$ cd ..
+$ cd a
+$ ls
+2 y
+$ cd /
+$ ls
+dir a
+dir b
+5 x";
+        let issues = validate_transcript(transcript);
+        assert_eq!(issues, vec![
+            TranscriptIssue::UnvisitedDirectory { line: 4, path: String::from("/b") },
+            TranscriptIssue::AboveRoot { line: 6 },
+            TranscriptIssue::UnlistedDirectory { line: 7, path: String::from("/c") },
+            TranscriptIssue::ConflictingListing { line: 13, path: String::from("/"), first_listed: 2 },
+        ]);
+        assert_eq!(issues[3].to_string(), "line 13: ls of / conflicts with the listing at line 2");
+    }
+
+    #[test]
+    fn test_replay_extended_dialect() {
+        let transcript = "$ cd /
+$ mkdir -p /x/y
+$ cd x/y
+$ ls
+10 z
+$ cd ../../a
+$ ls
+dir e
+29116 f
+$ cd /a/e/../..
+$ mv a/f x
+$ mv /x/y /x/w
+$ rm -rf a/e
+$ cd /x/w
+$ ls
+garbage
+$ cat z
+hello
+$ cd ../../..";
+        let (filesystem, warnings) = Filesystem::replay(transcript);
+        assert_eq!(render_tree(&filesystem), "- / (dir)
+  - a (dir)
+  - x (dir)
+    - f (file, size=29116)
+    - w (dir)
+      - z (file, size=10)");
+        let x = filesystem.root.subdirs().find(|dir| dir.name() == "x").unwrap();
+        assert_eq!(x.subdirs().next().unwrap().files().next().unwrap().path, "/x/w/z");
+        assert_eq!(warnings, vec![
+            ReplayWarning { line: 16, message: String::from("Unrecognised ls output: garbage") },
+            ReplayWarning { line: 17, message: String::from("Unknown command: cat z") },
+            ReplayWarning { line: 19, message: String::from("cd ../../.. goes above /") },
+        ]);
+        assert_eq!(validate_transcript(transcript), vec![
+            TranscriptIssue::UnlistedDirectory { line: 6, path: String::from("/a") },
+            TranscriptIssue::ConflictingListing { line: 15, path: String::from("/x/w"), first_listed: 4 },
+            TranscriptIssue::AboveRoot { line: 19 },
+        ]);
+    }
+
+    #[test]
+    fn test_replay_rejected_changes() {
+        let transcript = "$ ls
+dir a
+5 b
+$ mv a a/c
+$ mv a b
+$ rm /
+$ rm q
+$ mv q r
+$ cd b
+$ echo hi
+hi";
+        let (filesystem, warnings) = Filesystem::replay(transcript);
+        assert_eq!(filesystem, Filesystem::from_transcript("$ ls\ndir a\n5 b"));
+        assert_eq!(warnings.into_iter().map(|warning| warning.to_string()).collect_vec(), vec![
+            "line 4: Cannot move /a into itself",
+            "line 5: Cannot overwrite file /b with a directory",
+            "line 6: Refusing to remove /",
+            "line 7: No such file or directory: /q",
+            "line 8: No such file or directory: /q",
+            "line 9: /b is a file, not a directory",
+            "line 10: Unknown command: echo hi",
+        ]);
+    }
+
+    #[test]
+    fn test_plan_deletions() {
+        let filesystem = generator(EXAMPLE);
+        assert_eq!(plan_deletions(&filesystem, PUZZLE_DISK), Some(DeletionPlan {
+            paths: vec![String::from("/d")],
+            freed: 24933642,
+        }));
+        let geometry = DiskGeometry { capacity: 48381165, required_free: 90000 };
+        assert_eq!(plan_deletions(&filesystem, geometry), Some(DeletionPlan {
+            paths: vec![String::from("/a")],
+            freed: 94853,
+        }));
+        let roomy = DiskGeometry { capacity: 100000000, required_free: 30000000 };
+        assert_eq!(plan_deletions(&filesystem, roomy), Some(DeletionPlan { paths: vec![], freed: 0 }));
+        let tiny = DiskGeometry { capacity: 10, required_free: 20 };
+        assert_eq!(plan_deletions(&filesystem, tiny), None);
+    }
+
+    #[test]
+    fn test_plan_deletions_combines_dirs() {
+        let transcript = "$ cd /
+$ ls
+dir a
+dir b
+dir c
+$ cd a
+$ ls
+600 x
+$ cd ../b
+$ ls
+500 y
+dir n
+$ cd n
+$ ls
+450 z
+$ cd /c
+$ ls
+1000 w";
+        let filesystem = generator(transcript);
+        let geometry = DiskGeometry { capacity: 2550, required_free: 1050 };
+        assert_eq!(choose_deletion(&filesystem, geometry), Deletion::Delete { path: String::from("/"), size: 2550 });
+        assert_eq!(plan_deletions(&filesystem, geometry), Some(DeletionPlan {
+            paths: vec![String::from("/a"), String::from("/b/n")],
+            freed: 1050,
+        }));
+    }
+
+    #[test]
+    fn test_plan_deletions_large_sizes() {
+        // Terabyte-scale sizes would need gigabytes of bitset counted in bytes.
+        let gib: usize = 1 << 30;
+        let transcript = format!(
+            "$ cd /\n$ ls\ndir a\ndir b\ndir c\n$ cd a\n$ ls\n{} x\n$ cd ../b\n$ ls\n{} y\n$ cd ../c\n$ ls\n{} z",
+            600 * gib + 3, 500 * gib + 1, 1000 * gib + 7
+        );
+        let filesystem = generator(&transcript);
+        let total = filesystem.total_size();
+        let geometry = DiskGeometry { capacity: total + 100 * gib, required_free: 1200 * gib };
+        assert_eq!(plan_deletions(&filesystem, geometry), Some(DeletionPlan {
+            paths: vec![String::from("/a"), String::from("/b")],
+            freed: 1100 * gib + 4,
+        }));
+        let everything = DiskGeometry { capacity: total, required_free: total };
+        assert_eq!(plan_deletions(&filesystem, everything).unwrap().freed, total);
+        let too_much = DiskGeometry { capacity: total, required_free: total + 1 };
+        assert_eq!(plan_deletions(&filesystem, too_much), None);
+    }
+
+    #[test]
+    fn test_cd_root_mid_session() {
+        let transcript = format!("{}\n$ cd /\n$ cd a\n$ ls\ndir e\n29116 f\n2557 g\n62596 h.lst", EXAMPLE);
+        assert_eq!(generator(&transcript), generator(EXAMPLE));
+    }
+
+    #[test]
+    fn test_repeated_ls() {
+        let transcript = EXAMPLE.replacen("$ cd e\n$ ls\n584 i", "$ cd e\n$ ls\n584 i\n$ ls\n584 i", 1);
+        assert_eq!(solve_part1(&generator(&transcript)), 95437);
+        assert_eq!(generator(&transcript), generator(EXAMPLE));
     }
 }
\ No newline at end of file
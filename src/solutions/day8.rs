@@ -1,7 +1,9 @@
 use std::cmp::max;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
+use crate::solutions::netpbm;
+use crate::solutions::rng::Lcg;
 
 #[aoc_generator(day8)]
 pub fn generator(raw_input: &str) -> Vec<Vec<u8>> {
@@ -126,9 +128,320 @@ pub fn solve_part2(tree_heights: &[Vec<u8>]) -> usize {
     max_score
 }
 
+// Hands `on_distance` the distance from tree `i` of a line back to the first tree at
+// least as tall, or to the edge if there is none. Trees still waiting on the stack are
+// strictly shrinking, so each is pushed and popped once.
+fn for_each_viewing_distance<T: Ord + Copy>(
+    heights: impl Iterator<Item = (usize, T)>,
+    stack: &mut Vec<(usize, T)>,
+    mut on_distance: impl FnMut(usize, usize),
+) {
+    stack.clear();
+    for (step, (i, height)) in heights.enumerate() {
+        while stack.last().is_some_and(|&(_, blocker)| blocker < height) {
+            stack.pop();
+        }
+        on_distance(i, stack.last().map_or(step, |&(blocker_step, _)| step - blocker_step));
+        stack.push((step, height));
+    }
+}
+
+fn apply_viewing_distances<T: Ord + Copy>(
+    heights: impl Iterator<Item = (usize, T)>,
+    scores: &mut [usize],
+    stack: &mut Vec<(usize, T)>,
+) {
+    for_each_viewing_distance(heights, stack, |i, distance| scores[i] *= distance);
+}
+
+// Scenic score of every tree, row by row in one flat `Vec`.
+fn flat_scenic_scores(tree_heights: &[Vec<u8>]) -> Vec<usize> {
+    let rows = tree_heights.len();
+    let cols = tree_heights.first().unwrap().len();
+    let mut scores = vec![1usize; rows * cols];
+    let mut stack = Vec::with_capacity(rows.max(cols));
+    for (row, heights) in tree_heights.iter().enumerate() {
+        let line = heights.iter().copied().enumerate().map(|(col, h)| (row * cols + col, h));
+        apply_viewing_distances(line.clone(), &mut scores, &mut stack);
+        apply_viewing_distances(line.rev(), &mut scores, &mut stack);
+    }
+    for col in 0..cols {
+        let line = tree_heights.iter().enumerate().map(|(row, heights)| (row * cols + col, heights[col]));
+        apply_viewing_distances(line.clone(), &mut scores, &mut stack);
+        apply_viewing_distances(line.rev(), &mut scores, &mut stack);
+    }
+    scores
+}
+
+#[aoc(day8, part2, monotonic)]
+pub fn solve_part2_monotonic(tree_heights: &[Vec<u8>]) -> usize {
+    flat_scenic_scores(tree_heights).into_iter().max().unwrap()
+}
+
+pub fn scenic_scores(tree_heights: &[Vec<u8>]) -> Vec<Vec<usize>> {
+    let cols = tree_heights.first().unwrap().len();
+    flat_scenic_scores(tree_heights).chunks(cols).map(|row| row.to_vec()).collect_vec()
+}
+
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum Sightlines {
+    Axes,
+    WithDiagonals,
+}
+
+impl Sightlines {
+    fn directions(&self) -> &'static [(isize, isize)] {
+        match self {
+            Sightlines::Axes => &[(0, 1), (0, -1), (1, 0), (-1, 0)],
+            Sightlines::WithDiagonals => &[(0, 1), (0, -1), (1, 0), (-1, 0), (1, 1), (1, -1), (-1, 1), (-1, -1)]
+        }
+    }
+}
+
+// Every straight line of cells across a `rows` by `cols` grid heading in `direction`,
+// each starting from the edge it enters through.
+fn lines_across(rows: usize, cols: usize, (d_row, d_col): (isize, isize)) -> Vec<Vec<(usize, usize)>> {
+    let inside = |row: isize, col: isize| row >= 0 && col >= 0 && row < rows as isize && col < cols as isize;
+    (0..rows as isize).cartesian_product(0..cols as isize)
+        .filter(|&(row, col)| !inside(row - d_row, col - d_col))
+        .map(|(row, col)| {
+            (0..).map(|step| (row + step * d_row, col + step * d_col))
+                .take_while(|&(r, c)| inside(r, c))
+                .map(|(r, c)| (r as usize, c as usize))
+                .collect_vec()
+        }).collect_vec()
+}
+
+// Which trees can be seen from outside the forest along the given sightlines. Works on
+// any height type, so parsed elevations need no offset.
+pub fn visibility_map_with<T: Ord + Copy>(heights: &[Vec<T>], sightlines: Sightlines) -> Vec<Vec<bool>> {
+    let rows = heights.len();
+    let cols = heights.first().unwrap().len();
+    let mut visible = vec![vec![false; cols]; rows];
+    for &direction in sightlines.directions() {
+        for line in lines_across(rows, cols, direction) {
+            let mut tallest: Option<T> = None;
+            for (row, col) in line {
+                let height = heights[row][col];
+                if tallest.is_none_or(|tallest| height > tallest) {
+                    visible[row][col] = true;
+                    tallest = Some(height);
+                }
+            }
+        }
+    }
+    visible
+}
+
+pub fn scenic_scores_with<T: Ord + Copy>(heights: &[Vec<T>], sightlines: Sightlines) -> Vec<Vec<usize>> {
+    let rows = heights.len();
+    let cols = heights.first().unwrap().len();
+    let mut scores = vec![1usize; rows * cols];
+    let mut stack = Vec::with_capacity(rows.max(cols));
+    for &direction in sightlines.directions() {
+        for line in lines_across(rows, cols, direction) {
+            let cells = line.into_iter().map(|(row, col)| (row * cols + col, heights[row][col]));
+            apply_viewing_distances(cells, &mut scores, &mut stack);
+        }
+    }
+    scores.chunks(cols).map(|row| row.to_vec()).collect_vec()
+}
+
+pub fn count_visible<T: Ord + Copy>(heights: &[Vec<T>], sightlines: Sightlines) -> usize {
+    visibility_map_with(heights, sightlines).iter()
+        .map(|row| row.iter().filter(|&&seen| seen).count())
+        .sum()
+}
+
+pub fn best_scenic_score<T: Ord + Copy>(heights: &[Vec<T>], sightlines: Sightlines) -> usize {
+    scenic_scores_with(heights, sightlines).iter()
+        .filter_map(|row| row.iter().max())
+        .max()
+        .copied()
+        .unwrap()
+}
+
+// Elevation grids with whitespace-separated heights of any size, one row per line.
+pub fn parse_elevations(raw_input: &str) -> Vec<Vec<u32>> {
+    let grid = raw_input.lines()
+        .filter(|row| !row.trim().is_empty())
+        .map(|row| {
+            row.split_whitespace()
+                .map(|height| height.parse().unwrap_or_else(|_| panic!("Invalid height: {}", height)))
+                .collect_vec()
+        }).collect_vec();
+    assert!(grid.iter().map(Vec::len).all_equal(), "Every row must have the same number of heights");
+    grid
+}
+
+// Which trees can be seen from outside the forest, looking along rows and columns.
+pub fn visibility_map(tree_heights: &[Vec<u8>]) -> Vec<Vec<bool>> {
+    visibility_map_with(tree_heights, Sightlines::Axes)
+}
+
+// A forest that keeps its visible-tree count and best scenic score current as single
+// trees change height. Per tree it remembers whether it can be seen from, and how far
+// it can see towards, the left, right, top and bottom edges. A change only touches its
+// own row and column, so `set_height` costs O(rows + cols) plus the score bookkeeping.
+pub struct Forest<T: Ord + Copy> {
+    heights: Vec<Vec<T>>,
+    seen_from: Vec<Vec<[bool; 4]>>,
+    views: Vec<Vec<[usize; 4]>>,
+    visible: usize,
+    score_counts: BTreeMap<usize, usize>,
+}
+
+impl<T: Ord + Copy> Forest<T> {
+    pub fn new(heights: Vec<Vec<T>>) -> Forest<T> {
+        let rows = heights.len();
+        let cols = heights.first().unwrap().len();
+        let mut forest = Forest {
+            heights,
+            seen_from: vec![vec![[false; 4]; cols]; rows],
+            views: vec![vec![[0; 4]; cols]; rows],
+            visible: 0,
+            score_counts: BTreeMap::new(),
+        };
+        (0..rows).for_each(|row| forest.survey_row(row));
+        (0..cols).for_each(|col| forest.survey_col(col));
+        for (row, col) in (0..rows).cartesian_product(0..cols) {
+            forest.track(row, col, true);
+        }
+        forest
+    }
+
+    pub fn height(&self, row: usize, col: usize) -> T {
+        self.heights[row][col]
+    }
+
+    pub fn is_visible(&self, row: usize, col: usize) -> bool {
+        self.seen_from[row][col].iter().any(|&seen| seen)
+    }
+
+    pub fn scenic_score(&self, row: usize, col: usize) -> usize {
+        self.views[row][col].iter().product()
+    }
+
+    pub fn visible_count(&self) -> usize {
+        self.visible
+    }
+
+    pub fn best_scenic_score(&self) -> usize {
+        self.score_counts.keys().next_back().copied().unwrap_or(0)
+    }
+
+    pub fn set_height(&mut self, row: usize, col: usize, height: T) {
+        let cols = self.heights[row].len();
+        let affected = (0..cols).map(|c| (row, c))
+            .chain((0..self.heights.len()).filter(|&r| r != row).map(|r| (r, col)))
+            .collect_vec();
+        affected.iter().for_each(|&(r, c)| self.track(r, c, false));
+        self.heights[row][col] = height;
+        self.survey_row(row);
+        self.survey_col(col);
+        affected.iter().for_each(|&(r, c)| self.track(r, c, true));
+    }
+
+    // Adds a tree's visibility and score to the running totals, or takes them back out.
+    fn track(&mut self, row: usize, col: usize, add: bool) {
+        let (visible, score) = (self.is_visible(row, col) as usize, self.scenic_score(row, col));
+        if add {
+            self.visible += visible;
+            *self.score_counts.entry(score).or_insert(0) += 1;
+        } else {
+            self.visible -= visible;
+            let count = self.score_counts.get_mut(&score).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                self.score_counts.remove(&score);
+            }
+        }
+    }
+
+    // Walks `cells` from one edge, updating slot `side` of each tree's flags and views.
+    fn survey(&mut self, cells: impl Iterator<Item = (usize, usize)> + Clone, side: usize) {
+        let mut tallest: Option<T> = None;
+        for (row, col) in cells.clone() {
+            let height = self.heights[row][col];
+            self.seen_from[row][col][side] = tallest.is_none_or(|tallest| height > tallest);
+            tallest = tallest.max(Some(height));
+        }
+        let cols = self.heights[0].len();
+        let heights = &self.heights;
+        let views = &mut self.views;
+        let mut stack = Vec::new();
+        let line = cells.map(|(row, col)| (row * cols + col, heights[row][col]));
+        for_each_viewing_distance(line, &mut stack, |i, distance| {
+            views[i / cols][i % cols][side] = distance;
+        });
+    }
+
+    fn survey_row(&mut self, row: usize) {
+        let cols = self.heights[row].len();
+        self.survey((0..cols).map(|col| (row, col)), 0);
+        self.survey((0..cols).rev().map(|col| (row, col)), 1);
+    }
+
+    fn survey_col(&mut self, col: usize) {
+        let rows = self.heights.len();
+        self.survey((0..rows).map(|row| (row, col)), 2);
+        self.survey((0..rows).rev().map(|row| (row, col)), 3);
+    }
+}
+
+// The `count` best tree-house spots as `((row, col), score)`, best first. Ties go to
+// the tree nearest the top left.
+pub fn top_scenic_spots(tree_heights: &[Vec<u8>], count: usize) -> Vec<((usize, usize), usize)> {
+    let mut spots = scenic_scores(tree_heights).into_iter()
+        .enumerate()
+        .flat_map(|(row, scores)| scores.into_iter().enumerate().map(move |(col, score)| ((row, col), score)))
+        .collect_vec();
+    spots.sort_by(|(a_pos, a_score), (b_pos, b_score)| b_score.cmp(a_score).then(a_pos.cmp(b_pos)));
+    spots.truncate(count);
+    spots
+}
+
+// Visible trees keep their height digit, hidden ones become `.`. Heights carry the
+// generator's `+1` offset; anything that doesn't map back to a digit shows as `#`.
+pub fn render_visibility(tree_heights: &[Vec<u8>], visible: &[Vec<bool>]) -> String {
+    tree_heights.iter().zip(visible).map(|(heights, seen)| {
+        heights.iter().zip(seen).map(|(&height, &seen)| match seen {
+            true => height.checked_sub(1).and_then(|digit| char::from_digit(digit as u32, 10)).unwrap_or('#'),
+            false => '.'
+        }).collect::<String>()
+    }).join("\n")
+}
+
+// Greyscale PGM, scaling the largest value in `grid` to white.
+pub fn to_pgm(grid: &[Vec<usize>]) -> Vec<u8> {
+    let peak = grid.iter().flat_map(|row| row.iter()).copied().max().unwrap_or(0).max(1);
+    let pixels = grid.iter().flat_map(|row| row.iter()).map(|&value| (value * 255 / peak) as u8);
+    netpbm::pgm(grid.first().map_or(0, Vec::len), grid.len(), pixels)
+}
+
+// Colour PPM heat map running black, red, yellow, white from the smallest value in
+// `grid` to the largest.
+pub fn to_ppm_heatmap(grid: &[Vec<usize>]) -> Vec<u8> {
+    let peak = grid.iter().flat_map(|row| row.iter()).copied().max().unwrap_or(0).max(1);
+    let pixels = grid.iter().flat_map(|row| row.iter()).map(|&value| {
+        let heat = value * 765 / peak;
+        [heat.min(255), heat.clamp(255, 510) - 255, heat.max(510) - 510].map(|c| c as u8)
+    });
+    netpbm::ppm(grid.first().map_or(0, Vec::len), grid.len(), pixels)
+}
+
+// A `rows` by `cols` forest of random digit heights in the puzzle's input format.
+pub fn generate_forest(rows: usize, cols: usize, seed: u64) -> String {
+    let mut rng = Lcg::new(seed);
+    (0..rows).map(|_| {
+        (0..cols).map(|_| char::from(b'0' + rng.below(10) as u8)).collect::<String>()
+    }).join("\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::solutions::rng::assert_solvers_agree;
 
     const EXAMPLE: &str = "30373
 25512
@@ -159,4 +472,125 @@ mod tests {
         let example: Vec<Vec<u8>> = generator(&EXAMPLE);
         assert_eq!(solve_part2(&example), 8);
     }
+
+    #[test]
+    fn test_visibility_map() {
+        let example: Vec<Vec<u8>> = generator(EXAMPLE);
+        let visible = visibility_map(&example);
+        assert_eq!(visible.iter().flat_map(|row| row.iter()).filter(|&&seen| seen).count(), 21);
+        assert_eq!(render_visibility(&example, &visible), "30373
+255.2
+65.32
+3.5.9
+35390");
+        assert_eq!(render_visibility(&[vec![0, 1, 11]], &[vec![true, true, true]]), "#0#");
+    }
+
+    #[test]
+    fn test_scenic_scores() {
+        let example: Vec<Vec<u8>> = generator(EXAMPLE);
+        let scores = scenic_scores(&example);
+        assert_eq!(scores[1][2], 4);
+        assert_eq!(scores[3][2], 8);
+        assert_eq!(top_scenic_spots(&example, 3), vec![((3, 2), 8), ((2, 1), 6), ((1, 2), 4)]);
+    }
+
+    #[test]
+    fn test_heatmaps() {
+        let grid = vec![vec![0, 2], vec![4, 8]];
+        let pgm = to_pgm(&grid);
+        assert!(pgm.starts_with(b"P5\n2 2\n255\n"));
+        assert_eq!(&pgm[pgm.len() - 4..], &[0, 63, 127, 255]);
+        let ppm = to_ppm_heatmap(&grid);
+        assert!(ppm.starts_with(b"P6\n2 2\n255\n"));
+        assert_eq!(&ppm[ppm.len() - 12..], &[0, 0, 0, 191, 0, 0, 255, 127, 0, 255, 255, 255]);
+    }
+
+    fn naive_view(heights: &[Vec<u32>], row: usize, col: usize, (d_row, d_col): (isize, isize)) -> (bool, usize) {
+        let (mut r, mut c, mut distance) = (row as isize, col as isize, 0);
+        loop {
+            r += d_row;
+            c += d_col;
+            if r < 0 || c < 0 || r >= heights.len() as isize || c >= heights[0].len() as isize {
+                return (true, distance);
+            }
+            distance += 1;
+            if heights[r as usize][c as usize] >= heights[row][col] {
+                return (false, distance);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sightlines() {
+        let example: Vec<Vec<u8>> = generator(EXAMPLE);
+        assert_eq!(count_visible(&example, Sightlines::Axes), solve_part1(&example));
+        assert_eq!(best_scenic_score(&example, Sightlines::Axes), solve_part2(&example));
+        for seed in 0..5 {
+            let forest = parse_elevations(&generate_forest(12, 17, seed).replace("", " "));
+            for sightlines in [Sightlines::Axes, Sightlines::WithDiagonals] {
+                let (mut visible, mut best) = (0, 0);
+                for (row, col) in (0..12).cartesian_product(0..17) {
+                    let views = sightlines.directions().iter()
+                        .map(|&direction| naive_view(&forest, row, col, direction))
+                        .collect_vec();
+                    visible += views.iter().any(|&(seen, _)| seen) as usize;
+                    best = best.max(views.iter().map(|&(_, distance)| distance).product());
+                }
+                assert_eq!(count_visible(&forest, sightlines), visible);
+                assert_eq!(best_scenic_score(&forest, sightlines), best);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_elevations() {
+        let elevations = parse_elevations("30 0 30 70 30
+20 50 50 10 20
+60 50 30 30 20
+30 30 50 40 90
+30 50 30 90 0");
+        assert_eq!(elevations[3][4], 90);
+        assert_eq!(count_visible(&elevations, Sightlines::Axes), 21);
+        assert_eq!(best_scenic_score(&elevations, Sightlines::Axes), 8);
+        assert_eq!(count_visible(&elevations, Sightlines::WithDiagonals), 22);
+        assert_eq!(parse_elevations("1024 7\n  3 65536  \n"), vec![vec![1024, 7], vec![3, 65536]]);
+    }
+
+    #[test]
+    fn test_forest_set_height() {
+        let mut example: Vec<Vec<u8>> = generator(EXAMPLE);
+        let mut forest = Forest::new(example.clone());
+        assert_eq!(forest.visible_count(), 21);
+        assert_eq!(forest.best_scenic_score(), 8);
+        forest.set_height(3, 2, 1);
+        example[3][2] = 1;
+        assert_eq!(forest.best_scenic_score(), solve_part2(&example));
+        assert!(!forest.is_visible(3, 2));
+
+        let mut expected = generator(&generate_forest(20, 25, 9));
+        let mut forest = Forest::new(expected.clone());
+        let mut rng = Lcg::new(41);
+        for _ in 0..200 {
+            let (row, col, height) = (rng.below(20), rng.below(25), rng.below(10) as u8 + 1);
+            forest.set_height(row, col, height);
+            expected[row][col] = height;
+            assert_eq!(forest.height(row, col), height);
+            assert_eq!(forest.visible_count(), solve_part1(&expected));
+            assert_eq!(forest.best_scenic_score(), solve_part2(&expected));
+        }
+    }
+
+    #[test]
+    fn test_solve_part2_monotonic() {
+        let example: Vec<Vec<u8>> = generator(EXAMPLE);
+        assert_eq!(solve_part2_monotonic(&example), 8);
+        let shapes = [(1, 1), (1, 30), (30, 1), (40, 60), (99, 99)];
+        assert_solvers_agree(
+            0..shapes.len() as u64,
+            |seed| generator(&generate_forest(shapes[seed as usize].0, shapes[seed as usize].1, seed)),
+            |forest| solve_part2(forest),
+            |forest| solve_part2_monotonic(forest),
+        );
+    }
 }
\ No newline at end of file
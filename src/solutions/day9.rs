@@ -1,88 +1,449 @@
+use std::array;
 use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::Hash;
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
 use lazy_static::lazy_static;
-use num::{abs, signum};
+use num::{abs, signum, PrimInt, Signed};
+use crate::solutions::netpbm;
 
 lazy_static! {
-    static ref D_MAP: HashMap<char, (i16, i16)> = HashMap::from([
-        ('D', (0,-1)),
-        ('U', (0,1)),
-        ('R', (1,0)),
-        ('L', (-1,0))
+    static ref D_MAP: HashMap<&'static str, (i16, i16)> = HashMap::from([
+        ("D", (0,-1)),
+        ("U", (0,1)),
+        ("R", (1,0)),
+        ("L", (-1,0)),
+        ("UL", (-1,1)),
+        ("UR", (1,1)),
+        ("DL", (-1,-1)),
+        ("DR", (1,-1))
     ]);
 }
 
-fn move_head_knot(knot: (i16, i16), direction: &char) -> (i16, i16) {
-    let movement = D_MAP.get(direction).unwrap();
-    return (knot.0 + movement.0, knot.1 + movement.1);
+// The knot-following rule in any number of dimensions: once the knot ahead is more
+// than one cell away along some axis, step one cell towards it along every axis.
+// A following knot only ever moves towards a knot already in range, so it cannot
+// overflow; only the head needs checking.
+fn follow_knot<T: Coordinate, const N: usize>(prev_knot: &[T; N], knot: [T; N]) -> [T; N] {
+    if !causes_movement(prev_knot, &knot) {
+        return knot;
+    }
+    step_towards(prev_knot, knot)
 }
 
-fn move_trailing_knot(
-    prev_knot: &(i16, i16),
-    knot: (i16, i16)
-) -> (i16, i16) {
-    if !causes_movement(*prev_knot, &knot) {
-        return knot;
+fn step_towards<T: Coordinate, const N: usize>(prev_knot: &[T; N], knot: [T; N]) -> [T; N] {
+    array::from_fn(|axis| knot[axis] + signum(prev_knot[axis] - knot[axis]))
+}
+
+fn causes_movement<T: Coordinate, const N: usize>(new_knot_position: &[T; N], next_knot: &[T; N]) -> bool {
+    new_knot_position.iter().zip(next_knot).any(|(&a, &b)| abs(a - b) > T::one())
+}
+
+fn simulate_rope(movements: &Vec<(&str, i16)>, rope_length: usize) -> usize {
+    simulate_rope_checked::<i32>(movements, rope_length).unwrap_or_else(|e| panic!("{}", e))
+}
+
+// Anything signed and integral can hold a knot position.
+pub trait Coordinate: PrimInt + Signed + Hash {}
+
+impl<T: PrimInt + Signed + Hash> Coordinate for T {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RopeOverflow {
+    // 1-based position of the command that pushed the head, or the gap between two
+    // knots, past the coordinate range.
+    pub command: usize,
+}
+
+impl fmt::Display for RopeOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "command {}: rope left the coordinate range", self.command)
     }
-    let head_movement = (signum(prev_knot.0 - knot.0), signum(prev_knot.1 - knot.1));
-    return (knot.0 + head_movement.0, knot.1 + head_movement.1);
 }
 
-fn causes_movement(new_knot_position: (i16, i16), next_knot: &(i16, i16)) -> bool {
-    let x_stretch = abs(new_knot_position.0 - next_knot.0) > 1;
-    let y_stretch = abs(new_knot_position.1 - next_knot.1) > 1;
-    return x_stretch || y_stretch;
+// Every cell a tail has visited, as 64x64 bitmap tiles keyed by their corner. A tail
+// only ever steps to a touching cell, so its path fills tiles densely and a long walk
+// costs a bit per cell rather than a hashed entry.
+pub struct VisitedCells<T: Coordinate> {
+    tiles: HashMap<(T, T), [u64; 64]>,
+    count: usize,
+}
+
+impl<T: Coordinate> VisitedCells<T> {
+    pub fn new() -> VisitedCells<T> {
+        VisitedCells { tiles: HashMap::new(), count: 0 }
+    }
+
+    fn locate(cell: (T, T)) -> ((T, T), usize, usize) {
+        let mask = T::from(63).unwrap();
+        let offset = |a: T| (a & mask).to_usize().unwrap();
+        ((cell.0 >> 6, cell.1 >> 6), offset(cell.1), offset(cell.0))
+    }
+
+    // Returns whether the cell was new.
+    pub fn insert(&mut self, cell: (T, T)) -> bool {
+        let (tile, row, col) = Self::locate(cell);
+        let bits = &mut self.tiles.entry(tile).or_insert([0; 64])[row];
+        let fresh = *bits & (1 << col) == 0;
+        *bits |= 1 << col;
+        self.count += fresh as usize;
+        fresh
+    }
+
+    pub fn contains(&self, cell: (T, T)) -> bool {
+        let (tile, row, col) = Self::locate(cell);
+        self.tiles.get(&tile).is_some_and(|bits| bits[row] & (1 << col) != 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
 }
 
-fn move_rope(rope: &Vec<(i16, i16)>, head_movement: &char) -> Vec<(i16, i16)> {
-    let mut new_rope_position: Vec<(i16, i16)> = Vec::with_capacity(rope.len());
-    new_rope_position.insert(0, move_head_knot(rope[0], head_movement));
-    for i in 1..rope.len() {
-        let knot = &rope[i];
-        let prev_knot = &new_rope_position[i-1];
-        let knot_new_pos = move_trailing_knot(prev_knot, *knot);
-        new_rope_position.insert(i, knot_new_pos);
+impl<T: Coordinate> Default for VisitedCells<T> {
+    fn default() -> Self {
+        Self::new()
     }
-    assert_eq!(rope.len(), new_rope_position.len());
-    new_rope_position
 }
 
-fn simulate_rope(movements: &Vec<(char, i16)>, rope_length: usize) -> usize {
-    let mut rope: Vec<(i16, i16)> = (0..rope_length).map(|_| {(0,0)}).collect_vec();
-    let mut t_locs: HashSet<(i16, i16)> = HashSet::from([
-        rope.last().unwrap().clone()
-    ]);
-    movements.iter().foreach(|(d, c)| {
+// `simulate_rope` with knots held in `T`, reporting the command that would take the
+// head past the range of `T` instead of wrapping or panicking.
+pub fn simulate_rope_checked<T: Coordinate>(movements: &[(&str, i16)], rope_length: usize) -> Result<usize, RopeOverflow> {
+    simulate_elastic_rope::<T>(movements, &RopeModel::puzzle(rope_length))
+}
+
+// `simulate_rope_checked` for a rope whose knots follow the rules in `model`.
+pub fn simulate_elastic_rope<T: Coordinate>(movements: &[(&str, i16)], model: &RopeModel) -> Result<usize, RopeOverflow> {
+    let mut t_locs = VisitedCells::new();
+    t_locs.insert((T::zero(), T::zero()));
+    walk_rope(movements, model, |rope: &[[T; 2]], _| {
+        let [x, y] = *rope.last().unwrap();
+        t_locs.insert((x, y));
+    })?;
+    Ok(t_locs.len())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Chebyshev,
+    Manhattan,
+    Euclidean,
+}
+
+impl Metric {
+    pub fn distance<T: Coordinate>(&self, a: &[T; 2], b: &[T; 2]) -> f64 {
+        // Subtracting in `T` could overflow once slack lets knots drift far apart.
+        let [dx, dy] = [0, 1].map(|axis| (a[axis].to_f64().unwrap() - b[axis].to_f64().unwrap()).abs());
+        match self {
+            Metric::Chebyshev => dx.max(dy),
+            Metric::Manhattan => dx + dy,
+            Metric::Euclidean => dx.hypot(dy),
+        }
+    }
+}
+
+// How the knots of a rope follow each other: a knot stays put while it is within
+// `slack[k]` of the knot ahead under `metric`, and otherwise steps towards it until it
+// is back within reach. `slack` has an entry per knot, head included; the head's is
+// ignored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RopeModel {
+    pub metric: Metric,
+    pub slack: Vec<f64>,
+}
+
+impl RopeModel {
+    // Checks every slack is a finite, non-negative distance.
+    pub fn new(metric: Metric, slack: Vec<f64>) -> Result<RopeModel, String> {
+        match slack.iter().position(|s| !s.is_finite() || *s < 0.0) {
+            Some(k) => Err(format!("slack for knot {} must be finite and non-negative, got {}", k, slack[k])),
+            None => Ok(RopeModel { metric, slack }),
+        }
+    }
+
+    // Every knot given the same slack, which must be finite and non-negative.
+    pub fn uniform(metric: Metric, slack: f64, rope_length: usize) -> RopeModel {
+        RopeModel::new(metric, vec![slack; rope_length]).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    // The puzzle's rope: knots move once they are more than one cell from the knot ahead.
+    pub fn puzzle(rope_length: usize) -> RopeModel {
+        RopeModel::uniform(Metric::Chebyshev, 1.0, rope_length)
+    }
+
+    pub fn len(&self) -> usize {
+        self.slack.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slack.is_empty()
+    }
+
+    fn is_taut<T: Coordinate>(&self, k: usize, ahead: &[T; 2], knot: &[T; 2]) -> bool {
+        self.metric.distance(ahead, knot) > self.slack[k]
+    }
+}
+
+// How far apart `a` and `b` are, if that fits in `T`.
+fn gap<T: Coordinate>(a: T, b: T) -> Option<T> {
+    if a >= b { a.checked_sub(&b) } else { b.checked_sub(&a) }
+}
+
+// Drives a rope starting at the origin through `movements`, handing `on_step` the rope
+// after every step along with how far (in Chebyshev distance) each knot had fallen
+// behind the one ahead before catching up. The head's entry is always zero.
+fn walk_rope<T: Coordinate>(
+    movements: &[(&str, i16)],
+    model: &RopeModel,
+    mut on_step: impl FnMut(&[[T; 2]], &[T]),
+) -> Result<(), RopeOverflow> {
+    let mut rope: Vec<[T; 2]> = vec![[T::zero(); 2]; model.len()];
+    let mut stretches: Vec<T> = vec![T::zero(); model.len()];
+    for (i, (d, c)) in movements.iter().enumerate() {
+        let movement = D_MAP.get(d).unwrap();
+        let step = [T::from(movement.0).unwrap(), T::from(movement.1).unwrap()];
         for _ in 0..*c {
-            rope = move_rope(&rope, d);
-            t_locs.insert(rope.last().unwrap().clone());
+            let head = [rope[0][0].checked_add(&step[0]), rope[0][1].checked_add(&step[1])];
+            match head {
+                [Some(x), Some(y)] => rope[0] = [x, y],
+                _ => return Err(RopeOverflow { command: i + 1 }),
+            }
+            for k in 1..rope.len() {
+                let (ahead, knot) = (rope[k - 1], rope[k]);
+                stretches[k] = match [0, 1].map(|axis| gap(ahead[axis], knot[axis])) {
+                    [Some(dx), Some(dy)] => dx.max(dy),
+                    _ => return Err(RopeOverflow { command: i + 1 }),
+                };
+                // A knot sitting on the one ahead can't get any closer, whatever the slack.
+                while model.is_taut(k, &ahead, &rope[k]) && rope[k] != ahead {
+                    rope[k] = step_towards(&ahead, rope[k]);
+                }
+            }
+            on_step(&rope, &stretches);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnotStats<T: Coordinate> {
+    pub visited: usize,
+    // Corners of the smallest box holding every cell the knot visited.
+    pub min: (T, T),
+    pub max: (T, T),
+    // The furthest the knot fell behind the one ahead of it; zero for the head.
+    pub max_stretch: T,
+}
+
+// Statistics for every knot of the rope, head first, from a single simulation.
+pub fn knot_stats<T: Coordinate>(movements: &[(&str, i16)], rope_length: usize) -> Result<Vec<KnotStats<T>>, RopeOverflow> {
+    let origin = (T::zero(), T::zero());
+    let mut visited: Vec<VisitedCells<T>> = (0..rope_length).map(|_| VisitedCells::new()).collect_vec();
+    visited.iter_mut().for_each(|cells| { cells.insert(origin); });
+    let mut stats = vec![KnotStats { visited: 1, min: origin, max: origin, max_stretch: T::zero() }; rope_length];
+    walk_rope(movements, &RopeModel::puzzle(rope_length), |rope: &[[T; 2]], stretches| {
+        for (k, &[x, y]) in rope.iter().enumerate() {
+            let knot = &mut stats[k];
+            visited[k].insert((x, y));
+            knot.min = (knot.min.0.min(x), knot.min.1.min(y));
+            knot.max = (knot.max.0.max(x), knot.max.1.max(y));
+            knot.max_stretch = knot.max_stretch.max(stretches[k]);
+        }
+    })?;
+    for (knot, cells) in stats.iter_mut().zip(&visited) {
+        knot.visited = cells.len();
+    }
+    Ok(stats)
+}
+
+// Tab-separated table of `knot_stats`, one row per knot under a header row.
+pub fn render_knot_stats<T: Coordinate + fmt::Display>(stats: &[KnotStats<T>]) -> String {
+    let rows = stats.iter().enumerate().map(|(k, knot)| {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            knot_label(k, stats.len()), knot.visited, knot.min.0, knot.min.1, knot.max.0, knot.max.1, knot.max_stretch
+        )
+    });
+    std::iter::once("knot\tvisited\tmin_x\tmin_y\tmax_x\tmax_y\tmax_stretch".to_string()).chain(rows).join("\n")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramePace {
+    PerStep,
+    PerCommand,
+}
+
+// The puzzle's label for knot `i`: `H` for the head, `T` for the tail of a two-knot
+// rope, otherwise the knot's number.
+fn knot_label(i: usize, rope_length: usize) -> char {
+    match i {
+        0 => 'H',
+        _ if rope_length == 2 => 'T',
+        _ => char::from_digit(i as u32, 36).unwrap_or('*'),
+    }
+}
+
+// One frame of text per step or per command, starting with the untouched rope, all
+// drawn over the same window so they can be played back in order. Each cell shows the
+// lowest-numbered knot on it, then `s` for the start, then `#` for cells the tail has
+// visited so far. Knots follow `model`, and a walk past the range of `T` is an error.
+pub fn animate_rope<T: Coordinate>(
+    movements: &[(&str, i16)],
+    model: &RopeModel,
+    pace: FramePace,
+) -> Result<Vec<String>, RopeOverflow> {
+    let mut shown = movements.iter()
+        .flat_map(|&(_, c)| (0..c).map(move |step| pace == FramePace::PerStep || step + 1 == c));
+    let mut steps: Vec<(Vec<[T; 2]>, bool)> = vec![(vec![[T::zero(); 2]; model.len()], true)];
+    walk_rope(movements, model, |rope: &[[T; 2]], _| steps.push((rope.to_vec(), shown.next().unwrap())))?;
+    let knots = || steps.iter().flat_map(|(rope, _)| rope.iter());
+    let (min_x, max_x) = knots().map(|k| k[0]).minmax().into_option().unwrap();
+    let (min_y, max_y) = knots().map(|k| k[1]).minmax().into_option().unwrap();
+    // Taken in i128, so a window spanning the whole of `T` still measures up.
+    let offset = |from: T, to: T| (to.to_i128().unwrap() - from.to_i128().unwrap()) as usize;
+    let cell = |[x, y]: [T; 2]| (offset(y, max_y), offset(min_x, x));
+
+    let mut visited = vec![vec![false; offset(min_x, max_x) + 1]; offset(min_y, max_y) + 1];
+    let mut frames = Vec::new();
+    for (rope, shown) in &steps {
+        let (row, col) = cell(*rope.last().unwrap());
+        visited[row][col] = true;
+        if !shown {
+            continue;
+        }
+        let mut frame = visited.iter()
+            .map(|row| row.iter().map(|&seen| if seen { '#' } else { '.' }).collect_vec())
+            .collect_vec();
+        let (row, col) = cell([T::zero(); 2]);
+        frame[row][col] = 's';
+        for (i, &knot) in rope.iter().enumerate().rev() {
+            let (row, col) = cell(knot);
+            frame[row][col] = knot_label(i, rope.len());
         }
+        frames.push(frame.iter().map(|row| row.iter().collect::<String>()).join("\n"));
+    }
+    Ok(frames)
+}
+
+// Colour PPM of one animation frame, each cell blown up to a `scale` pixel square:
+// head red, other knots yellow, start blue, visited cells grey.
+pub fn frame_to_ppm(frame: &str, scale: usize) -> Vec<u8> {
+    let rows = frame.lines().collect_vec();
+    let width = rows.first().map_or(0, |row| row.len());
+    let pixels = rows.iter().flat_map(|row| {
+        let line = row.chars().flat_map(|c| {
+            let colour: [u8; 3] = match c {
+                'H' => [255, 0, 0],
+                's' => [0, 0, 255],
+                '#' => [128, 128, 128],
+                '.' => [0, 0, 0],
+                _ => [255, 255, 0],
+            };
+            std::iter::repeat_n(colour, scale)
+        }).collect_vec();
+        std::iter::repeat_n(line, scale).flat_map(|pixels| pixels.into_iter())
     });
-    t_locs.len()
+    netpbm::ppm(width * scale, rows.len() * scale, pixels)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighbourhood {
+    // Steps across one face of the cube: six directions.
+    Faces,
+    // Steps to any touching cube, including along edges and through corners: twenty-six.
+    All,
+}
+
+impl Neighbourhood {
+    pub fn directions(&self) -> Vec<[i16; 3]> {
+        (0..3).map(|_| -1..=1).multi_cartesian_product()
+            .map(|step| [step[0], step[1], step[2]])
+            .filter(|step| match self {
+                Neighbourhood::Faces => step.iter().map(|a| abs(*a)).sum::<i16>() == 1,
+                Neighbourhood::All => step != &[0, 0, 0],
+            })
+            .collect_vec()
+    }
+}
+
+// 3D moves such as `UF 3` or `DLB 1`: R/L along x, U/D along y and F/B along z, each
+// axis at most once. Directions outside `neighbourhood` are rejected.
+pub fn parse_moves_3d(raw_input: &str, neighbourhood: Neighbourhood) -> Result<Vec<([i16; 3], i16)>, String> {
+    let allowed = neighbourhood.directions();
+    raw_input.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()).map(|(i, line)| {
+        let (d_str, c_str) = line.split_whitespace().collect_tuple()
+            .ok_or(format!("line {}: expected a direction and a count", i + 1))?;
+        let mut step = [0; 3];
+        for letter in d_str.chars() {
+            let (axis, delta) = match letter {
+                'R' => (0, 1), 'L' => (0, -1),
+                'U' => (1, 1), 'D' => (1, -1),
+                'F' => (2, 1), 'B' => (2, -1),
+                _ => return Err(format!("line {}: unknown direction '{}'", i + 1, letter)),
+            };
+            if step[axis] != 0 {
+                return Err(format!("line {}: direction '{}' moves along one axis twice", i + 1, d_str));
+            }
+            step[axis] = delta;
+        }
+        if !allowed.contains(&step) {
+            return Err(format!("line {}: direction '{}' is not a {:?} neighbour", i + 1, d_str, neighbourhood));
+        }
+        let count = c_str.parse().map_err(|_| format!("line {}: bad count '{}'", i + 1, c_str))?;
+        Ok((step, count))
+    }).collect()
 }
 
+// `simulate_rope_checked` for a rope in space, with the same following rule for every knot.
+pub fn simulate_rope_3d<T: Coordinate>(movements: &[([i16; 3], i16)], rope_length: usize) -> Result<usize, RopeOverflow> {
+    let mut rope: Vec<[T; 3]> = vec![[T::zero(); 3]; rope_length];
+    let mut t_locs: HashSet<[T; 3]> = HashSet::from([*rope.last().unwrap()]);
+    for (i, (step, count)) in movements.iter().enumerate() {
+        let step: [T; 3] = step.map(|delta| T::from(delta).unwrap());
+        for _ in 0..*count {
+            let head: [Option<T>; 3] = array::from_fn(|axis| rope[0][axis].checked_add(&step[axis]));
+            rope[0] = match head {
+                [Some(x), Some(y), Some(z)] => [x, y, z],
+                _ => return Err(RopeOverflow { command: i + 1 }),
+            };
+            for i in 1..rope.len() {
+                rope[i] = follow_knot(&rope[i - 1], rope[i]);
+            }
+            t_locs.insert(*rope.last().unwrap());
+        }
+    }
+    Ok(t_locs.len())
+}
+
+// Directions are the keys of `D_MAP`, so the four diagonals `UL`, `UR`, `DL` and `DR`
+// are accepted alongside the puzzle's `U`, `D`, `L` and `R`.
 #[aoc_generator(day9)]
-pub fn generator(raw_input: &str) -> Vec<(char, i16)> {
+pub fn generator(raw_input: &str) -> Vec<(&'static str, i16)> {
     raw_input.split("\n").map(|r| {
         let (d_str, c_str): (&str, &str) = r.split(" ").collect_tuple().unwrap();
-        (d_str.chars().into_iter().rev().last().unwrap(), c_str.parse().unwrap())
+        (*D_MAP.get_key_value(d_str).unwrap().0, c_str.parse().unwrap())
     }).collect_vec()
 }
 
 #[aoc(day9, part1)]
-pub fn solve_part1(movements: &Vec<(char, i16)>) -> usize {
+pub fn solve_part1(movements: &Vec<(&str, i16)>) -> usize {
     simulate_rope(movements, 2)
 }
 
 #[aoc(day9, part2)]
-pub fn solve_part2(movements: &Vec<(char, i16)>) -> usize {
+pub fn solve_part2(movements: &Vec<(&str, i16)>) -> usize {
     simulate_rope(movements, 10)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::solutions::rng::Lcg;
 
     const EXAMPLE: &str = "R 4
 U 4
@@ -105,33 +466,177 @@ U 20";
     #[test]
     fn test_generator() {
         let expected = vec![
-            ('R', 4),
-            ('U', 4),
-            ('L', 3),
-            ('D', 1),
-            ('R', 4),
-            ('D', 1),
-            ('L', 5),
-            ('R', 2),
+            ("R", 4),
+            ("U", 4),
+            ("L", 3),
+            ("D", 1),
+            ("R", 4),
+            ("D", 1),
+            ("L", 5),
+            ("R", 2),
         ];
         assert_eq!(generator(&EXAMPLE), expected);
     }
 
     #[test]
     fn test_solve_part1() {
-        let example: Vec<(char, i16)> = generator(&EXAMPLE);
+        let example: Vec<(&str, i16)> = generator(&EXAMPLE);
         assert_eq!(solve_part1(&example), 13);
     }
 
     #[test]
     fn test_solve_part2() {
-        let example: Vec<(char, i16)> = generator(&EXAMPLE);
+        let example: Vec<(&str, i16)> = generator(&EXAMPLE);
         assert_eq!(solve_part2(&example), 1);
     }
 
     #[test]
     fn test2_solve_part2() {
-        let example: Vec<(char, i16)> = generator(&EXAMPLE2);
+        let example: Vec<(&str, i16)> = generator(&EXAMPLE2);
         assert_eq!(solve_part2(&example), 36);
     }
+
+    #[test]
+    fn test_diagonal_moves() {
+        let example = generator("UR 3\nDL 1\nR 2");
+        assert_eq!(example, vec![("UR", 3), ("DL", 1), ("R", 2)]);
+        // The tail trails the head diagonally to (2, 2), waits out the step back, then
+        // follows it right to (3, 2).
+        assert_eq!(solve_part1(&example), 4);
+        assert_eq!(animate_rope::<i32>(&example, &RopeModel::puzzle(2), FramePace::PerCommand).unwrap()[3], ".....\n..#TH\n.#...\ns....");
+    }
+
+    #[test]
+    fn test_rope_3d() {
+        assert_eq!(Neighbourhood::Faces.directions().len(), 6);
+        assert_eq!(Neighbourhood::All.directions().len(), 26);
+
+        // A flat 3D rope behaves exactly like the puzzle's.
+        let flat = parse_moves_3d(EXAMPLE2, Neighbourhood::Faces).unwrap();
+        assert_eq!(simulate_rope_3d::<i32>(&flat, 10), Ok(36));
+        assert_eq!(simulate_rope_3d::<i32>(&parse_moves_3d(EXAMPLE, Neighbourhood::Faces).unwrap(), 2), Ok(13));
+
+        let climb = parse_moves_3d("F 2\nUF 3\nDLB 5", Neighbourhood::All).unwrap();
+        assert_eq!(climb[1], ([0, 1, 1], 3));
+        assert_eq!(simulate_rope_3d::<i32>(&climb, 2), Ok(9));
+        let dive = parse_moves_3d("B 100\nF 50\nB 100", Neighbourhood::Faces).unwrap();
+        assert_eq!(simulate_rope_3d::<i8>(&dive, 2), Err(RopeOverflow { command: 3 }));
+        assert_eq!(simulate_rope_3d::<i16>(&dive, 2), Ok(150));
+
+        assert!(parse_moves_3d("UF 3", Neighbourhood::Faces).unwrap_err().contains("line 1"));
+        assert!(parse_moves_3d("R 1\nUD 1", Neighbourhood::All).unwrap_err().contains("line 2"));
+        assert!(parse_moves_3d("R 1\nX 1", Neighbourhood::All).is_err());
+    }
+
+    #[test]
+    fn test_simulate_rope_checked() {
+        let example: Vec<(&str, i16)> = generator(EXAMPLE2);
+        assert_eq!(simulate_rope_checked::<i8>(&example, 10), Ok(36));
+        assert_eq!(simulate_rope_checked::<i64>(&example, 10), Ok(36));
+
+        let moves = vec![("R", 100), ("L", 200), ("L", 100)];
+        assert_eq!(simulate_rope_checked::<i8>(&moves, 2), Err(RopeOverflow { command: 3 }));
+        assert_eq!(simulate_rope_checked::<i16>(&moves, 2), Ok(299));
+        assert_eq!(RopeOverflow { command: 2 }.to_string(), "command 2: rope left the coordinate range");
+
+        // Well past where an i16 would wrap.
+        let long_walk = vec![("UR", 30000); 4];
+        assert_eq!(simulate_rope_checked::<i16>(&long_walk, 10), Err(RopeOverflow { command: 2 }));
+        assert_eq!(simulate_rope_checked::<i32>(&long_walk, 10), Ok(4 * 30000 - 8));
+    }
+
+    #[test]
+    fn test_knot_stats() {
+        let example: Vec<(&str, i16)> = generator(EXAMPLE2);
+        let stats = knot_stats::<i32>(&example, 10).unwrap();
+        assert_eq!(stats.len(), 10);
+        for length in 2..=10 {
+            assert_eq!(stats[length - 1].visited, simulate_rope(&example, length));
+        }
+        assert_eq!(stats[0].min, (-11, -5));
+        assert_eq!(stats[0].max, (14, 15));
+        assert_eq!(stats[0].max_stretch, 0);
+        assert_eq!(stats[1].max_stretch, 2);
+        assert_eq!(stats[9].min, (-11, -5));
+        assert_eq!(stats[9].max, (10, 6));
+
+        let table = render_knot_stats(&knot_stats::<i32>(&generator(EXAMPLE), 2).unwrap());
+        assert_eq!(table, "knot\tvisited\tmin_x\tmin_y\tmax_x\tmax_y\tmax_stretch\n\
+                           H\t21\t0\t0\t5\t4\t0\n\
+                           T\t13\t0\t0\t4\t4\t2");
+    }
+
+    #[test]
+    fn test_elastic_rope() {
+        let example: Vec<(&str, i16)> = generator(EXAMPLE2);
+        assert_eq!(simulate_elastic_rope::<i32>(&example, &RopeModel::puzzle(10)), Ok(36));
+        // Diagonal neighbours are sqrt(2) away, so a 1.5 radius is the puzzle's square.
+        assert_eq!(simulate_elastic_rope::<i32>(&example, &RopeModel::uniform(Metric::Euclidean, 1.5, 10)), Ok(36));
+        // With no slack every knot sits on the head.
+        let head_cells = knot_stats::<i32>(&example, 1).unwrap()[0].visited;
+        assert_eq!(simulate_elastic_rope::<i32>(&example, &RopeModel::uniform(Metric::Manhattan, 0.0, 10)), Ok(head_cells));
+
+        let mut rng = Lcg::new(46);
+        for metric in [Metric::Chebyshev, Metric::Manhattan, Metric::Euclidean] {
+            let slack = (0..10).map(|_| rng.below(7) as f64 / 2.0).collect_vec();
+            let model = RopeModel { metric, slack };
+            walk_rope(&example, &model, |rope: &[[i32; 2]], _| {
+                for k in 1..rope.len() {
+                    assert!(metric.distance(&rope[k - 1], &rope[k]) <= model.slack[k]);
+                }
+            }).unwrap();
+        }
+        // Slack below zero is refused, and still can't stall a hand-built model.
+        assert!(RopeModel::new(Metric::Chebyshev, vec![0.0, -1.0]).unwrap_err().contains("knot 1"));
+        assert!(RopeModel::new(Metric::Euclidean, vec![0.0, f64::NAN]).is_err());
+        let negative = RopeModel { metric: Metric::Chebyshev, slack: vec![0.0, -1.0] };
+        assert_eq!(simulate_elastic_rope::<i32>(&example, &negative), Ok(head_cells));
+
+        // Slack wider than `T` can span leaves the stretch itself out of range.
+        let wide = RopeModel::uniform(Metric::Chebyshev, 300.0, 2);
+        assert_eq!(simulate_elastic_rope::<i8>(&[("L", 127)], &wide), Ok(1));
+        assert_eq!(simulate_elastic_rope::<i8>(&[("L", 128)], &wide), Err(RopeOverflow { command: 1 }));
+        assert_eq!(Metric::Chebyshev.distance(&[i8::MIN, 0], &[i8::MAX, 0]), 255.0);
+
+        assert_eq!(Metric::Manhattan.distance(&[0, 0], &[-3, 4]), 7.0);
+        assert_eq!(Metric::Euclidean.distance(&[0, 0], &[-3, 4]), 5.0);
+        assert_eq!(Metric::Chebyshev.distance(&[0, 0], &[-3, 4]), 4.0);
+    }
+
+    #[test]
+    fn test_visited_cells() {
+        let mut visited: VisitedCells<i32> = VisitedCells::new();
+        assert!(visited.is_empty());
+        for cell in [(0, 0), (-1, 0), (63, 64), (-64, -65), (i32::MIN, i32::MAX)] {
+            assert!(visited.insert(cell));
+            assert!(!visited.insert(cell));
+            assert!(visited.contains(cell));
+        }
+        assert_eq!(visited.len(), 5);
+        assert!(!visited.contains((0, -1)));
+        assert!(!visited.contains((64, 63)));
+    }
+
+    #[test]
+    fn test_animate_rope() {
+        let example: Vec<(&str, i16)> = generator(EXAMPLE);
+        let frames = animate_rope::<i32>(&example, &RopeModel::puzzle(2), FramePace::PerCommand).unwrap();
+        assert_eq!(frames.len(), example.len() + 1);
+        assert_eq!(frames[0], "......\n......\n......\n......\nH.....");
+        assert_eq!(frames[1], "......\n......\n......\n......\ns##TH.");
+        assert_eq!(frames[8], "..##..\n...##.\n.TH##.\n....#.\ns###..");
+
+        let steps = example.iter().map(|(_, c)| *c as usize).sum::<usize>();
+        let frames = animate_rope::<i32>(&example, &RopeModel::puzzle(10), FramePace::PerStep).unwrap();
+        assert_eq!(frames.len(), steps + 1);
+        assert_eq!(frames[frames.len() - 1], "......\n......\n.1H3..\n.5....\n6.....");
+        let overrun = vec![("R", 100), ("L", 200), ("L", 100)];
+        assert_eq!(animate_rope::<i8>(&overrun, &RopeModel::puzzle(2), FramePace::PerCommand), Err(RopeOverflow { command: 3 }));
+        let slack = animate_rope::<i32>(&generator("R 3"), &RopeModel::uniform(Metric::Chebyshev, 2.0, 2), FramePace::PerCommand);
+        assert_eq!(slack.unwrap()[1], "sT.H");
+
+        let image = frame_to_ppm(&frames[0], 2);
+        assert!(image.starts_with(b"P6\n12 10\n255\n"));
+        assert_eq!(image.len(), b"P6\n12 10\n255\n".len() + 12 * 10 * 3);
+    }
 }
\ No newline at end of file
@@ -10,3 +10,5 @@ pub mod day9;
 pub mod day10;
 pub mod day11;
 pub mod day12;
+pub mod rng;
+pub mod netpbm;
@@ -0,0 +1,15 @@
+// Binary Netpbm images: greyscale PGM (P5) and colour PPM (P6), both a short text
+// header followed by raw 8-bit samples in row order.
+fn encode(magic: &str, width: usize, height: usize, samples: impl IntoIterator<Item = u8>) -> Vec<u8> {
+    let mut image = format!("{}\n{} {}\n255\n", magic, width, height).into_bytes();
+    image.extend(samples);
+    image
+}
+
+pub fn pgm(width: usize, height: usize, pixels: impl IntoIterator<Item = u8>) -> Vec<u8> {
+    encode("P5", width, height, pixels)
+}
+
+pub fn ppm(width: usize, height: usize, pixels: impl IntoIterator<Item = [u8; 3]>) -> Vec<u8> {
+    encode("P6", width, height, pixels.into_iter().flatten())
+}
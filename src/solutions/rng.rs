@@ -0,0 +1,33 @@
+// Seeded pseudo-random numbers for the input generators and randomised tests: a
+// 64-bit linear congruential generator with Knuth's MMIX constants. The same seed
+// always gives the same sequence, so generated inputs are reproducible.
+pub struct Lcg {
+    state: u64,
+}
+
+impl Lcg {
+    pub fn new(seed: u64) -> Lcg {
+        Lcg { state: seed }
+    }
+
+    // A value in `0..bound`, taken from the high bits where an LCG is most random.
+    pub fn below(&mut self, bound: usize) -> usize {
+        self.state = self.state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        ((self.state >> 33) as usize) % bound
+    }
+}
+
+// Checks a solver against the one it is meant to match on the input generated from
+// each seed.
+#[cfg(test)]
+pub fn assert_solvers_agree<I, T: PartialEq + std::fmt::Debug>(
+    seeds: impl IntoIterator<Item = u64>,
+    generate: impl Fn(u64) -> I,
+    expected: impl Fn(&I) -> T,
+    actual: impl Fn(&I) -> T,
+) {
+    for seed in seeds {
+        let input = generate(seed);
+        assert_eq!(actual(&input), expected(&input), "seed {}", seed);
+    }
+}